@@ -0,0 +1,3 @@
+ALTER TABLE audio DROP COLUMN comment;
+ALTER TABLE audio DROP COLUMN genre;
+ALTER TABLE audio DROP COLUMN album;
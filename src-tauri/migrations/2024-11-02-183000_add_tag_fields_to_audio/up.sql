@@ -0,0 +1,3 @@
+ALTER TABLE audio ADD COLUMN album TEXT;
+ALTER TABLE audio ADD COLUMN genre TEXT;
+ALTER TABLE audio ADD COLUMN comment TEXT;
@@ -0,0 +1 @@
+ALTER TABLE audio DROP COLUMN artwork_hash;
@@ -0,0 +1 @@
+ALTER TABLE audio ADD COLUMN artwork_hash TEXT;
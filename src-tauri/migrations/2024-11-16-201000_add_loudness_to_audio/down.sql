@@ -0,0 +1,3 @@
+ALTER TABLE audio DROP COLUMN true_peak;
+ALTER TABLE audio DROP COLUMN loudness_range;
+ALTER TABLE audio DROP COLUMN integrated_lufs;
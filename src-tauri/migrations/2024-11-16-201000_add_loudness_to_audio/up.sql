@@ -0,0 +1,3 @@
+ALTER TABLE audio ADD COLUMN integrated_lufs DOUBLE;
+ALTER TABLE audio ADD COLUMN loudness_range DOUBLE;
+ALTER TABLE audio ADD COLUMN true_peak DOUBLE;
@@ -0,0 +1 @@
+ALTER TABLE audio DROP COLUMN source_audio_id;
@@ -0,0 +1 @@
+ALTER TABLE audio ADD COLUMN source_audio_id INTEGER REFERENCES audio(id);
@@ -0,0 +1,2 @@
+ALTER TABLE audio DROP COLUMN musical_key;
+ALTER TABLE audio DROP COLUMN bpm;
@@ -0,0 +1,2 @@
+ALTER TABLE audio ADD COLUMN bpm DOUBLE;
+ALTER TABLE audio ADD COLUMN musical_key TEXT;
@@ -0,0 +1,5 @@
+CREATE TABLE recipe (
+  id INTEGER PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  steps TEXT NOT NULL
+);
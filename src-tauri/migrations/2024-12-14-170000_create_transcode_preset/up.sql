@@ -0,0 +1,13 @@
+CREATE TABLE transcode_preset (
+  id INTEGER PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL UNIQUE,
+  options TEXT NOT NULL
+);
+
+INSERT INTO transcode_preset (name, options) VALUES
+  ('MP3 320 kbps', '{"format":"mp3","codec":"mp3","bitrate_kbps":320}'),
+  ('MP3 V0', '{"format":"mp3","codec":"mp3","vbr_quality":0}'),
+  ('WAV 48 kHz 24-bit', '{"format":"wav","codec":"pcm","sample_rate":48000,"bit_depth":24}'),
+  ('FLAC 16-bit', '{"format":"flac","codec":"flac","bit_depth":16}'),
+  ('Opus 128 kbps', '{"format":"opus","codec":"opus","bitrate_kbps":128}'),
+  ('AAC 256 kbps', '{"format":"m4a","codec":"aac","bitrate_kbps":256}');
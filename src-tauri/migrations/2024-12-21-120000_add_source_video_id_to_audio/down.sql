@@ -0,0 +1,2 @@
+DROP INDEX audio_source_video_id;
+ALTER TABLE audio DROP COLUMN source_video_id;
@@ -0,0 +1,2 @@
+ALTER TABLE audio ADD COLUMN source_video_id TEXT;
+CREATE INDEX audio_source_video_id ON audio (source_video_id);
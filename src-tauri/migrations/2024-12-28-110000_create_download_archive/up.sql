@@ -0,0 +1,9 @@
+CREATE TABLE download_archive (
+  id INTEGER PRIMARY KEY NOT NULL,
+  extractor TEXT NOT NULL,
+  video_id TEXT NOT NULL,
+  title TEXT,
+  audio_id INTEGER,
+  downloaded_on TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (extractor, video_id)
+);
@@ -0,0 +1,3 @@
+ALTER TABLE audio DROP COLUMN source_end;
+ALTER TABLE audio DROP COLUMN source_start;
+ALTER TABLE audio DROP COLUMN source_url;
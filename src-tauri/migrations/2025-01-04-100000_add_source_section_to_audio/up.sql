@@ -0,0 +1,3 @@
+ALTER TABLE audio ADD COLUMN source_url TEXT;
+ALTER TABLE audio ADD COLUMN source_start REAL;
+ALTER TABLE audio ADD COLUMN source_end REAL;
@@ -0,0 +1,3 @@
+DROP INDEX audio_source;
+CREATE INDEX audio_source_video_id ON audio (source_video_id);
+ALTER TABLE audio DROP COLUMN source_extractor;
@@ -0,0 +1,5 @@
+ALTER TABLE audio ADD COLUMN source_extractor TEXT;
+-- every download so far came from YouTube
+UPDATE audio SET source_extractor = 'youtube' WHERE source_video_id IS NOT NULL;
+DROP INDEX audio_source_video_id;
+CREATE INDEX audio_source ON audio (source_extractor, source_video_id);
@@ -0,0 +1 @@
+ALTER TABLE audio DROP COLUMN music_analyzed;
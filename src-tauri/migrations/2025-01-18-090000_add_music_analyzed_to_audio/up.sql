@@ -0,0 +1,3 @@
+ALTER TABLE audio ADD COLUMN music_analyzed BOOLEAN NOT NULL DEFAULT 0;
+-- entries with a tempo were either analyzed already or set by hand
+UPDATE audio SET music_analyzed = 1 WHERE bpm IS NOT NULL;
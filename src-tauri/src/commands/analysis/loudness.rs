@@ -0,0 +1,197 @@
+use std::ops::ControlFlow;
+use diesel::prelude::*;
+use ebur128::{EbuR128, Mode};
+use serde::{Serialize, Deserialize};
+use crate::db::establish_connection;
+use crate::helper::decoder::{decode_with_fallback, AudioInfo, DecodedAudio, SampleSink};
+use crate::models::audio_model::Audio;
+
+// silence measures as -inf, which cannot be stored or serialized; report the floor instead
+pub const LOUDNESS_FLOOR: f64 = -70.0;
+pub const TRUE_PEAK_FLOOR: f64 = -144.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessMeasurement {
+  // integrated loudness in LUFS
+  pub integrated_lufs: f64,
+  // loudness range in LU
+  pub loudness_range: f64,
+  // maximum true peak across channels in dBTP
+  pub true_peak: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoudnessResult {
+  pub audio_id: i32,
+  pub measurement: Option<LoudnessMeasurement>,
+  pub error: Option<String>,
+}
+
+fn finite_or(value: f64, floor: f64) -> f64 {
+  if value.is_finite() { value.max(floor) } else { floor }
+}
+
+// EBU R128 meter fed while the file is decoded
+pub struct LoudnessMeter {
+  meter: EbuR128,
+  channels: u32,
+  error: Option<String>,
+}
+
+impl LoudnessMeter {
+  pub fn new(info: &AudioInfo) -> Result<Self, String> {
+    let channels: u32 = info.channels.max(1) as u32;
+    let meter: EbuR128 = EbuR128::new(channels, info.sample_rate, Mode::I | Mode::LRA | Mode::TRUE_PEAK)
+      .map_err(|e| format!("Unable to create loudness meter: {:?}", e))?;
+    Ok(LoudnessMeter { meter, channels, error: None })
+  }
+}
+
+impl SampleSink for LoudnessMeter {
+  type Output = LoudnessMeasurement;
+
+  fn push(&mut self, samples: &[f32]) -> ControlFlow<()> {
+    match self.meter.add_frames_f32(samples) {
+      Ok(()) => ControlFlow::Continue(()),
+      Err(err) => {
+        self.error = Some(format!("Unable to measure loudness: {:?}", err));
+        ControlFlow::Break(())
+      }
+    }
+  }
+
+  fn finish(self, _info: &AudioInfo) -> Result<LoudnessMeasurement, String> {
+    if let Some(err) = self.error {
+      return Err(err);
+    }
+    let integrated: f64 = self.meter.loudness_global().map_err(|e| format!("Unable to measure loudness: {:?}", e))?;
+    let range: f64 = self.meter.loudness_range().map_err(|e| format!("Unable to measure loudness range: {:?}", e))?;
+    let mut peak: f64 = 0.0;
+    for channel in 0..self.channels {
+      let channel_peak: f64 = self.meter.true_peak(channel).map_err(|e| format!("Unable to measure true peak: {:?}", e))?;
+      peak = peak.max(channel_peak);
+    }
+
+    Ok(LoudnessMeasurement {
+      integrated_lufs: finite_or(integrated, LOUDNESS_FLOOR),
+      loudness_range: finite_or(range, 0.0),
+      true_peak: finite_or(20.0 * peak.log10(), TRUE_PEAK_FLOOR),
+    })
+  }
+}
+
+pub fn measure_loudness(decoded: &DecodedAudio) -> Result<LoudnessMeasurement, String> {
+  let info: AudioInfo = decoded.info();
+  let mut meter: LoudnessMeter = LoudnessMeter::new(&info)?;
+  let _ = meter.push(&decoded.samples);
+  meter.finish(&info)
+}
+
+pub async fn measure_file_loudness(file_path: &str) -> Result<LoudnessMeasurement, String> {
+  decode_with_fallback(file_path, LoudnessMeter::new).await
+}
+
+pub fn store_loudness(audio_id_arg: i32, measurement: &LoudnessMeasurement) -> Result<(), String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::update(audio.find(audio_id_arg))
+    .set((
+      integrated_lufs.eq(measurement.integrated_lufs),
+      loudness_range.eq(measurement.loudness_range),
+      true_peak.eq(measurement.true_peak),
+    ))
+    .execute(&mut connection)
+    .map_err(|e| format!("Error updating audio entry: {}", e))?;
+  Ok(())
+}
+
+pub async fn analyze_and_store_loudness(audio_id_arg: i32, file_path: &str) -> Result<LoudnessMeasurement, String> {
+  let measurement: LoudnessMeasurement = measure_file_loudness(file_path).await?;
+  store_loudness(audio_id_arg, &measurement)?;
+  Ok(measurement)
+}
+
+// measures the given entries, or every entry without a measurement when no ids are given.
+// `force` re-measures entries that were already analyzed
+#[tauri::command(async)]
+pub async fn analyze_loudness(audio_ids: Option<Vec<i32>>, force: Option<bool>) -> Result<Vec<LoudnessResult>, String> {
+  use crate::schema::audio::dsl::*;
+
+  let targets: Vec<Audio> = {
+    let mut connection: SqliteConnection = establish_connection();
+    let mut query = audio.into_boxed();
+    if let Some(ids) = audio_ids {
+      query = query.filter(id.eq_any(ids));
+    }
+    if !force.unwrap_or(false) {
+      query = query.filter(integrated_lufs.is_null());
+    }
+    query.load::<Audio>(&mut connection).map_err(|e| format!("Error querying audio: {}", e))?
+  };
+
+  let mut results: Vec<LoudnessResult> = Vec::with_capacity(targets.len());
+  for target in targets {
+    let result = analyze_and_store_loudness(target.id, &target.path).await;
+    results.push(match result {
+      Ok(measurement) => LoudnessResult { audio_id: target.id, measurement: Some(measurement), error: None },
+      Err(err) => LoudnessResult { audio_id: target.id, measurement: None, error: Some(err) },
+    });
+  }
+
+  Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::helper::decoder::test_utils::sine;
+
+  fn stereo(mono: &[f32]) -> Vec<f32> {
+    mono.iter().flat_map(|s| [*s, *s]).collect()
+  }
+
+  #[test]
+  fn test_measure_loudness_reference_sine() {
+    // EBU Tech 3341 case 1: a stereo 1 kHz sine at -23 dBFS measures -23 LUFS
+    let amplitude: f32 = 10f32.powf(-23.0 / 20.0);
+    let decoded = DecodedAudio {
+      sample_rate: 48000,
+      channels: 2,
+      samples: stereo(&sine(1000.0, 48000, 20.0, amplitude)),
+    };
+
+    let measurement = measure_loudness(&decoded).unwrap();
+    assert!((measurement.integrated_lufs + 23.0).abs() < 0.1, "{:?}", measurement);
+    assert!((measurement.true_peak + 23.0).abs() < 0.5, "{:?}", measurement);
+    assert!(measurement.loudness_range < 0.5, "{:?}", measurement);
+  }
+
+  #[test]
+  fn test_meter_fed_in_chunks_matches_whole_clip() {
+    let decoded = DecodedAudio { sample_rate: 44100, channels: 2, samples: stereo(&sine(440.0, 44100, 5.0, 0.3)) };
+    let info: AudioInfo = decoded.info();
+    let mut meter = LoudnessMeter::new(&info).unwrap();
+    for chunk in decoded.samples.chunks(4410) {
+      let _ = meter.push(chunk);
+    }
+    assert_eq!(meter.finish(&info).unwrap(), measure_loudness(&decoded).unwrap());
+  }
+
+  #[test]
+  fn test_measure_loudness_silence() {
+    let decoded = DecodedAudio { sample_rate: 48000, channels: 2, samples: vec![0.0; 48000 * 2 * 5] };
+    let measurement = measure_loudness(&decoded).unwrap();
+    assert_eq!(measurement.integrated_lufs, LOUDNESS_FLOOR);
+    assert_eq!(measurement.true_peak, TRUE_PEAK_FLOOR);
+  }
+
+  #[test]
+  fn test_measure_loudness_louder_signal_measures_higher() {
+    let quiet = DecodedAudio { sample_rate: 44100, channels: 1, samples: sine(440.0, 44100, 5.0, 0.1) };
+    let loud = DecodedAudio { sample_rate: 44100, channels: 1, samples: sine(440.0, 44100, 5.0, 0.5) };
+    let difference = measure_loudness(&loud).unwrap().integrated_lufs - measure_loudness(&quiet).unwrap().integrated_lufs;
+    assert!((difference - 20.0 * 5f64.log10()).abs() < 0.1);
+  }
+}
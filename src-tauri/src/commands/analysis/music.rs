@@ -0,0 +1,405 @@
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use diesel::prelude::*;
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Serialize, Deserialize};
+use crate::db::establish_connection;
+use crate::helper::decoder::{decode_with_fallback, AudioInfo, DecodedAudio, SampleSink};
+use crate::models::audio_model::Audio;
+
+pub const MIN_BPM: f64 = 60.0;
+pub const MAX_BPM: f64 = 200.0;
+// only the beginning of long files is analyzed, tempo and key rarely change enough to matter
+pub const MAX_ANALYSIS_SECONDS: usize = 240;
+
+const FRAME_SIZE: usize = 4096;
+const HOP_SIZE: usize = 512;
+
+const PITCH_CLASSES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+// Krumhansl-Kessler key profiles, starting at the tonic
+const MAJOR_PROFILE: [f64; 12] = [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f64; 12] = [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+static MUSIC_ANALYSIS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// clears MUSIC_ANALYSIS_RUNNING when the job ends, however it ends
+struct RunningGuard;
+
+impl Drop for RunningGuard {
+  fn drop(&mut self) {
+    MUSIC_ANALYSIS_RUNNING.store(false, Ordering::SeqCst);
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MusicAnalysis {
+  pub bpm: Option<f64>,
+  pub musical_key: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MusicAnalysisProgress {
+  pub audio_id: i32,
+  pub completed: usize,
+  pub total: usize,
+  pub analysis: Option<MusicAnalysis>,
+  pub error: Option<String>,
+}
+
+// magnitude spectra of Hann windowed frames
+fn spectrogram(samples: &[f32]) -> Vec<Vec<f32>> {
+  if samples.len() < FRAME_SIZE {
+    return Vec::new();
+  }
+
+  let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME_SIZE);
+  let window: Vec<f32> = (0..FRAME_SIZE)
+    .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME_SIZE as f32).cos())
+    .collect();
+
+  let mut frames: Vec<Vec<f32>> = Vec::with_capacity((samples.len() - FRAME_SIZE) / HOP_SIZE + 1);
+  let mut buffer: Vec<Complex<f32>> = vec![Complex::new(0.0, 0.0); FRAME_SIZE];
+  for start in (0..=samples.len() - FRAME_SIZE).step_by(HOP_SIZE) {
+    for (i, value) in buffer.iter_mut().enumerate() {
+      *value = Complex::new(samples[start + i] * window[i], 0.0);
+    }
+    fft.process(&mut buffer);
+    frames.push(buffer[..FRAME_SIZE / 2 + 1].iter().map(|c| c.norm()).collect());
+  }
+  frames
+}
+
+// spectral flux of log compressed magnitudes, one value per frame
+pub fn onset_envelope(spectra: &[Vec<f32>]) -> Vec<f32> {
+  let mut envelope: Vec<f32> = Vec::with_capacity(spectra.len());
+  for (t, frame) in spectra.iter().enumerate() {
+    let flux: f32 = match t {
+      0 => 0.0,
+      _ => frame.iter().zip(spectra[t - 1].iter())
+        .map(|(current, previous)| ((1.0 + 1000.0 * current).ln() - (1.0 + 1000.0 * previous).ln()).max(0.0))
+        .sum(),
+    };
+    envelope.push(flux);
+  }
+  envelope
+}
+
+// picks the autocorrelation peak of the onset envelope, weighted towards 120 BPM to avoid
+// half/double tempo errors, and refines it with parabolic interpolation
+pub fn estimate_bpm(envelope: &[f32], frame_rate: f64) -> Option<f64> {
+  let mean: f32 = envelope.iter().sum::<f32>() / envelope.len().max(1) as f32;
+  let centered: Vec<f64> = envelope.iter().map(|v| (v - mean) as f64).collect();
+
+  let min_lag: usize = (frame_rate * 60.0 / MAX_BPM).floor().max(1.0) as usize;
+  let max_lag: usize = (frame_rate * 60.0 / MIN_BPM).ceil() as usize;
+  if centered.len() <= max_lag + 1 {
+    return None;
+  }
+
+  let autocorrelation = |lag: usize| -> f64 {
+    let n: usize = centered.len() - lag;
+    (0..n).map(|i| centered[i] * centered[i + lag]).sum::<f64>() / n as f64
+  };
+  let values: Vec<f64> = (min_lag - 1..=max_lag + 1).map(autocorrelation).collect();
+
+  let mut best: Option<(usize, f64)> = None;
+  for lag in min_lag..=max_lag {
+    let value: f64 = values[lag - min_lag + 1];
+    if value <= 0.0 {
+      continue;
+    }
+    let bpm: f64 = 60.0 * frame_rate / lag as f64;
+    let weight: f64 = (-0.5 * (bpm / 120.0).log2().powi(2)).exp();
+    if best.is_none_or(|(_, score)| value * weight > score) {
+      best = Some((lag, value * weight));
+    }
+  }
+
+  let (lag, _) = best?;
+  let (previous, current, next) = (values[lag - min_lag], values[lag - min_lag + 1], values[lag - min_lag + 2]);
+  let denominator: f64 = previous - 2.0 * current + next;
+  let offset: f64 = if denominator.abs() > f64::EPSILON { 0.5 * (previous - next) / denominator } else { 0.0 };
+
+  Some(60.0 * frame_rate / (lag as f64 + offset.clamp(-0.5, 0.5)))
+}
+
+pub fn chroma(spectra: &[Vec<f32>], sample_rate: u32) -> [f64; 12] {
+  let mut chroma: [f64; 12] = [0.0; 12];
+  let bin_width: f64 = sample_rate as f64 / FRAME_SIZE as f64;
+  for frame in spectra {
+    for (bin, magnitude) in frame.iter().enumerate().skip(1) {
+      let frequency: f64 = bin as f64 * bin_width;
+      // C2 to C7, below that the bins are too coarse to tell semitones apart
+      if !(65.0..=2100.0).contains(&frequency) {
+        continue;
+      }
+      let semitones_from_a: i32 = (12.0 * (frequency / 440.0).log2()).round() as i32;
+      chroma[(semitones_from_a + 9).rem_euclid(12) as usize] += *magnitude as f64;
+    }
+  }
+  chroma
+}
+
+fn correlation(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+  let mean_a: f64 = a.iter().sum::<f64>() / 12.0;
+  let mean_b: f64 = b.iter().sum::<f64>() / 12.0;
+  let covariance: f64 = a.iter().zip(b).map(|(x, y)| (x - mean_a) * (y - mean_b)).sum();
+  let variance_a: f64 = a.iter().map(|x| (x - mean_a).powi(2)).sum();
+  let variance_b: f64 = b.iter().map(|y| (y - mean_b).powi(2)).sum();
+  covariance / (variance_a * variance_b).sqrt().max(f64::EPSILON)
+}
+
+// matches the chroma against every major and minor key profile, e.g. "A minor"
+pub fn estimate_key(chroma: &[f64; 12]) -> Option<String> {
+  if chroma.iter().all(|value| *value <= 0.0) {
+    return None;
+  }
+
+  let mut best: Option<(String, f64)> = None;
+  for tonic in 0..12 {
+    for (profile, mode) in [(&MAJOR_PROFILE, "major"), (&MINOR_PROFILE, "minor")] {
+      let mut rotated: [f64; 12] = [0.0; 12];
+      for (i, value) in profile.iter().enumerate() {
+        rotated[(tonic + i) % 12] = *value;
+      }
+      let score: f64 = correlation(chroma, &rotated);
+      if best.as_ref().is_none_or(|(_, best_score)| score > *best_score) {
+        best = Some((format!("{} {}", PITCH_CLASSES[tonic], mode), score));
+      }
+    }
+  }
+  best.map(|(key, _)| key)
+}
+
+fn analyze_mono(mono: &[f32], sample_rate: u32) -> MusicAnalysis {
+  let spectra: Vec<Vec<f32>> = spectrogram(mono);
+  let frame_rate: f64 = sample_rate as f64 / HOP_SIZE as f64;
+
+  MusicAnalysis {
+    bpm: estimate_bpm(&onset_envelope(&spectra), frame_rate).map(|bpm| (bpm * 10.0).round() / 10.0),
+    musical_key: estimate_key(&chroma(&spectra, sample_rate)),
+  }
+}
+
+pub fn analyze_music(decoded: &DecodedAudio) -> MusicAnalysis {
+  let mut mono: Vec<f32> = decoded.to_mono();
+  mono.truncate(MAX_ANALYSIS_SECONDS * decoded.sample_rate as usize);
+  analyze_mono(&mono, decoded.sample_rate)
+}
+
+// collects the mono mix of the first MAX_ANALYSIS_SECONDS and stops decoding there
+pub struct MusicAnalyzer {
+  channels: usize,
+  sample_rate: u32,
+  limit: usize,
+  mono: Vec<f32>,
+}
+
+impl MusicAnalyzer {
+  pub fn new(info: &AudioInfo) -> Self {
+    let limit: usize = MAX_ANALYSIS_SECONDS * info.sample_rate as usize;
+    MusicAnalyzer {
+      channels: info.channels.max(1) as usize,
+      sample_rate: info.sample_rate,
+      limit,
+      mono: Vec::with_capacity(limit.min(info.total_frames.unwrap_or(0) as usize)),
+    }
+  }
+}
+
+impl SampleSink for MusicAnalyzer {
+  type Output = MusicAnalysis;
+
+  fn push(&mut self, samples: &[f32]) -> ControlFlow<()> {
+    let wanted: usize = self.limit - self.mono.len();
+    self.mono.extend(samples.chunks(self.channels)
+      .take(wanted)
+      .map(|frame| frame.iter().sum::<f32>() / self.channels as f32));
+    if self.mono.len() >= self.limit { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+  }
+
+  fn finish(self, _info: &AudioInfo) -> Result<MusicAnalysis, String> {
+    Ok(analyze_mono(&self.mono, self.sample_rate))
+  }
+}
+
+pub async fn analyze_and_store_music(audio_id_arg: i32, file_path: &str) -> Result<MusicAnalysis, String> {
+  use crate::schema::audio::dsl::*;
+
+  let analysis: MusicAnalysis = decode_with_fallback(file_path, |info| Ok(MusicAnalyzer::new(info))).await?;
+
+  let mut connection: SqliteConnection = establish_connection();
+  diesel::update(audio.find(audio_id_arg))
+    .set((bpm.eq(analysis.bpm), musical_key.eq(analysis.musical_key.clone()), music_analyzed.eq(true)))
+    .execute(&mut connection)
+    .map_err(|e| format!("Error updating audio entry: {}", e))?;
+
+  Ok(analysis)
+}
+
+// entries the library pass analyzes: all of them with `force`, otherwise those without a tempo that
+// were never analyzed
+pub fn music_analysis_targets(force: bool) -> Result<Vec<Audio>, String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+  let mut query = audio.into_boxed();
+  if !force {
+    query = query.filter(bpm.is_null().and(music_analyzed.eq(false)));
+  }
+  query.load::<Audio>(&mut connection).map_err(|e| format!("Error querying audio: {}", e))
+}
+
+// starts a background pass over the library and returns right away. Entries that already have a tempo
+// (including manual overrides) or were analyzed before without one are skipped unless `force` is set;
+// progress is emitted as "music-analysis-progress" events and a "music-analysis-finished" event closes
+// the job
+#[tauri::command]
+pub fn analyze_music_library(window: tauri::Window, force: Option<bool>) -> Result<usize, String> {
+  if MUSIC_ANALYSIS_RUNNING.swap(true, Ordering::SeqCst) {
+    return Err("Music analysis is already running".to_string());
+  }
+  let running: RunningGuard = RunningGuard;
+
+  let targets: Vec<Audio> = music_analysis_targets(force.unwrap_or(false))?;
+  let total: usize = targets.len();
+
+  tauri::async_runtime::spawn(async move {
+    for (index, target) in targets.into_iter().enumerate() {
+      let result = analyze_and_store_music(target.id, &target.path).await;
+      let progress = MusicAnalysisProgress {
+        audio_id: target.id,
+        completed: index + 1,
+        total,
+        analysis: result.as_ref().ok().cloned(),
+        error: result.err(),
+      };
+      let _ = window.emit("music-analysis-progress", progress);
+    }
+    drop(running);
+    let _ = window.emit("music-analysis-finished", total);
+  });
+
+  Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::commands::audio_commands::insert_audio_entry;
+  use crate::db::test_utils::fresh_database;
+  use crate::helper::decoder::test_utils::{sine, write_test_wav};
+  use crate::models::audio_model::NewAudio;
+  use tempfile::tempdir;
+
+  fn click_track(bpm: f64, sample_rate: u32, seconds: f64) -> Vec<f32> {
+    let mut samples: Vec<f32> = vec![0.0; (sample_rate as f64 * seconds) as usize];
+    let interval: usize = (sample_rate as f64 * 60.0 / bpm) as usize;
+    let click: Vec<f32> = sine(1500.0, sample_rate, 0.03, 0.8);
+    for start in (0..samples.len()).step_by(interval) {
+      for (i, value) in click.iter().enumerate() {
+        if let Some(sample) = samples.get_mut(start + i) {
+          // decaying click
+          *sample += value * (1.0 - i as f32 / click.len() as f32);
+        }
+      }
+    }
+    samples
+  }
+
+  fn chord(frequencies: &[f32], sample_rate: u32, seconds: f32) -> Vec<f32> {
+    let mut samples: Vec<f32> = vec![0.0; (sample_rate as f32 * seconds) as usize];
+    for frequency in frequencies {
+      for (sample, value) in samples.iter_mut().zip(sine(*frequency, sample_rate, seconds, 0.2)) {
+        *sample += value;
+      }
+    }
+    samples
+  }
+
+  #[test]
+  fn test_estimate_bpm_click_track() {
+    for expected in [90.0, 120.0, 128.0, 174.0] {
+      let decoded = DecodedAudio { sample_rate: 22050, channels: 1, samples: click_track(expected, 22050, 30.0) };
+      let analysis = analyze_music(&decoded);
+      let bpm = analysis.bpm.unwrap();
+      assert!((bpm - expected).abs() < 1.5, "expected {} got {}", expected, bpm);
+    }
+  }
+
+  #[test]
+  fn test_estimate_bpm_too_short() {
+    let decoded = DecodedAudio { sample_rate: 22050, channels: 1, samples: vec![0.0; 22050] };
+    assert_eq!(analyze_music(&decoded).bpm, None);
+  }
+
+  #[test]
+  fn test_estimate_key_c_major() {
+    // C major triad and scale tones
+    let decoded = DecodedAudio {
+      sample_rate: 22050,
+      channels: 1,
+      samples: chord(&[261.63, 329.63, 392.00, 523.25, 293.66, 349.23, 440.00], 22050, 5.0),
+    };
+    assert_eq!(analyze_music(&decoded).musical_key, Some("C major".to_string()));
+  }
+
+  #[test]
+  fn test_estimate_key_a_minor() {
+    // A minor triad spread over three octaves
+    let decoded = DecodedAudio {
+      sample_rate: 22050,
+      channels: 1,
+      samples: chord(&[220.00, 261.63, 329.63, 440.00, 110.00], 22050, 5.0),
+    };
+    assert_eq!(analyze_music(&decoded).musical_key, Some("A minor".to_string()));
+  }
+
+  #[test]
+  fn test_analyzer_stops_at_the_limit() {
+    let sample_rate: u32 = 8000;
+    let info = AudioInfo { sample_rate, channels: 2, total_frames: None };
+    let mut analyzer = MusicAnalyzer::new(&info);
+    let chunk: Vec<f32> = vec![0.25; sample_rate as usize * 2 * 60];
+    let mut pushed: usize = 0;
+    while analyzer.push(&chunk).is_continue() {
+      pushed += 1;
+      assert!(pushed < 10, "decoding was not stopped");
+    }
+    assert_eq!(pushed + 1, MAX_ANALYSIS_SECONDS / 60);
+    assert_eq!(analyzer.mono.len(), MAX_ANALYSIS_SECONDS * sample_rate as usize);
+  }
+
+  #[test]
+  fn test_running_guard_clears_flag_on_panic() {
+    MUSIC_ANALYSIS_RUNNING.store(true, Ordering::SeqCst);
+    let result = std::panic::catch_unwind(|| {
+      let _running = RunningGuard;
+      panic!("analysis failed");
+    });
+    assert!(result.is_err());
+    assert!(!MUSIC_ANALYSIS_RUNNING.load(Ordering::SeqCst));
+  }
+
+  #[test]
+  fn test_analyzed_entry_without_tempo_is_not_analyzed_again() {
+    let _database = fresh_database();
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("silence.wav");
+    write_test_wav(&path, 22050, 1, &[0.0; 22050]);
+    let path: &str = path.to_str().unwrap();
+    let created = insert_audio_entry(&NewAudio { title: "silence", author: "", path, duration: "00:01", audio_type: "wav", ..Default::default() }).unwrap();
+
+    assert_eq!(music_analysis_targets(false).unwrap().len(), 1);
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let analysis = runtime.block_on(analyze_and_store_music(created.id, path)).unwrap();
+    assert_eq!(analysis.bpm, None);
+    assert!(music_analysis_targets(false).unwrap().is_empty());
+    assert_eq!(music_analysis_targets(true).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn test_estimate_key_silence() {
+    assert_eq!(estimate_key(&[0.0; 12]), None);
+  }
+}
@@ -0,0 +1,220 @@
+use std::ops::ControlFlow;
+use serde::{Serialize, Deserialize};
+use crate::commands::audio_commands::find_audio;
+use crate::helper::decoder::{decode_with_fallback, AudioInfo, DecodedAudio, SampleSink};
+use crate::models::audio_model::Audio;
+
+// loudness is measured over 10 ms windows
+const WINDOWS_PER_SECOND: usize = 100;
+
+// a range of the clip in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeRange {
+  pub start: f64,
+  pub end: f64,
+}
+
+impl TimeRange {
+  pub fn duration(&self) -> f64 {
+    self.end - self.start
+  }
+}
+
+// finds every range at least `min_duration` seconds long whose RMS level stays below `threshold_db` dBFS
+// while the file is decoded. The output is the silent ranges and the length of the clip in seconds
+pub struct SilenceDetector {
+  sample_rate: u32,
+  window_frames: usize,
+  // samples per window over all channels
+  window_samples: usize,
+  threshold: f64,
+  min_duration: f64,
+  // sum of squares and sample count of the window being filled
+  window_sum: f64,
+  window_len: usize,
+  windows: usize,
+  silence_start: Option<f64>,
+  ranges: Vec<TimeRange>,
+}
+
+impl SilenceDetector {
+  pub fn new(info: &AudioInfo, threshold_db: f64, min_duration: f64) -> Self {
+    let window_frames: usize = (info.sample_rate as usize / WINDOWS_PER_SECOND).max(1);
+    SilenceDetector {
+      sample_rate: info.sample_rate,
+      window_frames,
+      window_samples: window_frames * info.channels.max(1) as usize,
+      threshold: 10f64.powf(threshold_db / 20.0),
+      min_duration,
+      window_sum: 0.0,
+      window_len: 0,
+      windows: 0,
+      silence_start: None,
+      ranges: Vec::new(),
+    }
+  }
+
+  fn close_window(&mut self) {
+    let mean_square: f64 = self.window_sum / self.window_len as f64;
+    let window_start: f64 = (self.windows * self.window_frames) as f64 / self.sample_rate as f64;
+
+    match (mean_square.sqrt() < self.threshold, self.silence_start) {
+      (true, None) => self.silence_start = Some(window_start),
+      (false, Some(start)) => {
+        self.ranges.push(TimeRange { start, end: window_start });
+        self.silence_start = None;
+      }
+      _ => {}
+    }
+    self.windows += 1;
+    self.window_sum = 0.0;
+    self.window_len = 0;
+  }
+}
+
+impl SampleSink for SilenceDetector {
+  type Output = (Vec<TimeRange>, f64);
+
+  fn push(&mut self, samples: &[f32]) -> ControlFlow<()> {
+    for sample in samples {
+      self.window_sum += (*sample as f64).powi(2);
+      self.window_len += 1;
+      if self.window_len == self.window_samples {
+        self.close_window();
+      }
+    }
+    ControlFlow::Continue(())
+  }
+
+  fn finish(mut self, info: &AudioInfo) -> Result<(Vec<TimeRange>, f64), String> {
+    if self.window_len > 0 {
+      self.close_window();
+    }
+    let total: f64 = info.duration_secs().unwrap_or(0.0);
+    if let Some(start) = self.silence_start {
+      self.ranges.push(TimeRange { start, end: total });
+    }
+
+    let min_duration: f64 = self.min_duration;
+    self.ranges.retain(|range| range.duration() >= min_duration);
+    Ok((self.ranges, total))
+  }
+}
+
+pub fn find_silent_ranges(decoded: &DecodedAudio, threshold_db: f64, min_duration: f64) -> Vec<TimeRange> {
+  let info: AudioInfo = decoded.info();
+  let mut detector: SilenceDetector = SilenceDetector::new(&info, threshold_db, min_duration);
+  let _ = detector.push(&decoded.samples);
+  detector.finish(&info).map(|(ranges, _)| ranges).unwrap_or_default()
+}
+
+// the parts of a clip of `total` seconds between the silent ranges, dropping segments shorter than `min_segment_duration`
+pub fn non_silent_segments(silences: &[TimeRange], total: f64, min_segment_duration: f64) -> Vec<TimeRange> {
+  let mut segments: Vec<TimeRange> = Vec::with_capacity(silences.len() + 1);
+  let mut start: f64 = 0.0;
+  for silence in silences {
+    segments.push(TimeRange { start, end: silence.start });
+    start = silence.end;
+  }
+  segments.push(TimeRange { start, end: total });
+
+  segments.retain(|segment| segment.duration() > 0.0 && segment.duration() >= min_segment_duration);
+  segments
+}
+
+pub async fn detect_file_silence(file_path: &str, threshold_db: f64, min_duration: f64) -> Result<(Vec<TimeRange>, f64), String> {
+  decode_with_fallback(file_path, move |info| Ok(SilenceDetector::new(info, threshold_db, min_duration))).await
+}
+
+#[tauri::command(async)]
+pub async fn detect_silence(audio_id: i32, threshold_db: f64, min_duration: f64) -> Result<Vec<TimeRange>, String> {
+  let source: Audio = find_audio(audio_id)?;
+  let (silences, _) = detect_file_silence(&source.path, threshold_db, min_duration).await?;
+  Ok(silences)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::helper::decoder::test_utils::sine;
+
+  // 1 s tone, 2 s silence, 1 s tone, 0.3 s silence, 1 s tone
+  fn tone_with_gaps() -> DecodedAudio {
+    let sample_rate: u32 = 8000;
+    let mut samples: Vec<f32> = Vec::new();
+    samples.extend(sine(440.0, sample_rate, 1.0, 0.5));
+    samples.extend(vec![0.0; 2 * sample_rate as usize]);
+    samples.extend(sine(440.0, sample_rate, 1.0, 0.5));
+    samples.extend(vec![0.0; (0.3 * sample_rate as f32) as usize]);
+    samples.extend(sine(440.0, sample_rate, 1.0, 0.5));
+    DecodedAudio { sample_rate, channels: 1, samples }
+  }
+
+  fn assert_close(actual: &TimeRange, start: f64, end: f64) {
+    assert!((actual.start - start).abs() < 0.02 && (actual.end - end).abs() < 0.02, "{:?}", actual);
+  }
+
+  #[test]
+  fn test_find_silent_ranges() {
+    let silences = find_silent_ranges(&tone_with_gaps(), -50.0, 0.2);
+    assert_eq!(silences.len(), 2);
+    assert_close(&silences[0], 1.0, 3.0);
+    assert_close(&silences[1], 4.0, 4.3);
+  }
+
+  #[test]
+  fn test_find_silent_ranges_min_duration() {
+    let silences = find_silent_ranges(&tone_with_gaps(), -50.0, 1.0);
+    assert_eq!(silences.len(), 1);
+    assert_close(&silences[0], 1.0, 3.0);
+  }
+
+  #[test]
+  fn test_find_silent_ranges_threshold() {
+    // the tone itself is below a -3 dBFS threshold
+    let decoded = tone_with_gaps();
+    let silences = find_silent_ranges(&decoded, -3.0, 0.2);
+    assert_eq!(silences.len(), 1);
+    assert_close(&silences[0], 0.0, decoded.duration_secs());
+  }
+
+  #[test]
+  fn test_find_silent_ranges_trailing_silence() {
+    let mut samples: Vec<f32> = sine(440.0, 8000, 1.0, 0.5);
+    samples.extend(vec![0.0; 8000]);
+    let silences = find_silent_ranges(&DecodedAudio { sample_rate: 8000, channels: 1, samples }, -50.0, 0.5);
+    assert_eq!(silences.len(), 1);
+    assert_close(&silences[0], 1.0, 2.0);
+  }
+
+  #[test]
+  fn test_detector_chunking_does_not_matter() {
+    let decoded = tone_with_gaps();
+    let info: AudioInfo = decoded.info();
+    let mut detector = SilenceDetector::new(&info, -50.0, 0.2);
+    for chunk in decoded.samples.chunks(1153) {
+      let _ = detector.push(chunk);
+    }
+    let (silences, total) = detector.finish(&info).unwrap();
+    assert_eq!(silences, find_silent_ranges(&decoded, -50.0, 0.2));
+    assert_eq!(total, decoded.duration_secs());
+  }
+
+  #[test]
+  fn test_non_silent_segments() {
+    let silences = vec![TimeRange { start: 1.0, end: 3.0 }, TimeRange { start: 4.0, end: 4.3 }];
+    let segments = non_silent_segments(&silences, 5.3, 0.0);
+    assert_eq!(segments, vec![
+      TimeRange { start: 0.0, end: 1.0 },
+      TimeRange { start: 3.0, end: 4.0 },
+      TimeRange { start: 4.3, end: 5.3 },
+    ]);
+  }
+
+  #[test]
+  fn test_non_silent_segments_drops_edges_and_short_segments() {
+    let silences = vec![TimeRange { start: 0.0, end: 1.0 }, TimeRange { start: 1.5, end: 3.0 }, TimeRange { start: 6.0, end: 7.0 }];
+    let segments = non_silent_segments(&silences, 7.0, 1.0);
+    assert_eq!(segments, vec![TimeRange { start: 3.0, end: 6.0 }]);
+  }
+}
@@ -0,0 +1,350 @@
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use diesel::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::db::establish_connection;
+use crate::helper::constants::waveform_cache_path;
+use crate::helper::decoder::{decode_with_fallback, AudioInfo, SampleSink};
+use crate::models::audio_model::Audio;
+
+// zoom levels kept in the cache, each level merges 4 pixels of the previous one
+pub const WAVEFORM_LEVELS: [u32; 4] = [256, 1024, 4096, 16384];
+
+// min/max pairs per pixel, laid out like audiowaveform's JSON output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaveformData {
+  pub version: u32,
+  pub channels: u32,
+  pub sample_rate: u32,
+  pub samples_per_pixel: u32,
+  pub bits: u32,
+  pub length: u32,
+  pub data: Vec<i16>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WaveformCache {
+  pub levels: Vec<WaveformData>,
+}
+
+// identifies the file a cache entry was generated from without reading it; a file that is replaced or
+// changed gets a different size or modification time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileStamp {
+  pub path: String,
+  pub size: u64,
+  pub modified_ns: u64,
+}
+
+// what is written to the cache directory, one file per audio entry
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredWaveform {
+  stamp: FileStamp,
+  cache: WaveformCache,
+}
+
+// computes the min/max peaks of the mono mix of the file while it is decoded
+pub struct PeakBuilder {
+  channels: usize,
+  sample_rate: u32,
+  samples_per_pixel: u32,
+  // samples in the pixel being built and their extremes
+  count: u32,
+  min: i16,
+  max: i16,
+  data: Vec<i16>,
+}
+
+impl PeakBuilder {
+  pub fn new(info: &AudioInfo, samples_per_pixel: u32) -> Self {
+    PeakBuilder {
+      channels: info.channels.max(1) as usize,
+      sample_rate: info.sample_rate,
+      samples_per_pixel,
+      count: 0,
+      min: i16::MAX,
+      max: i16::MIN,
+      data: Vec::new(),
+    }
+  }
+
+  fn close_pixel(&mut self) {
+    self.data.push(self.min);
+    self.data.push(self.max);
+    self.count = 0;
+    self.min = i16::MAX;
+    self.max = i16::MIN;
+  }
+}
+
+impl SampleSink for PeakBuilder {
+  type Output = WaveformData;
+
+  fn push(&mut self, samples: &[f32]) -> ControlFlow<()> {
+    for frame in samples.chunks(self.channels) {
+      let mono: f32 = frame.iter().sum::<f32>() / self.channels as f32;
+      let sample: i16 = (mono.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+      self.min = self.min.min(sample);
+      self.max = self.max.max(sample);
+      self.count += 1;
+      if self.count == self.samples_per_pixel {
+        self.close_pixel();
+      }
+    }
+    ControlFlow::Continue(())
+  }
+
+  fn finish(mut self, _info: &AudioInfo) -> Result<WaveformData, String> {
+    if self.count > 0 {
+      self.close_pixel();
+    }
+    Ok(WaveformData {
+      version: 2,
+      channels: 1,
+      sample_rate: self.sample_rate,
+      samples_per_pixel: self.samples_per_pixel,
+      bits: 16,
+      length: (self.data.len() / 2) as u32,
+      data: self.data,
+    })
+  }
+}
+
+// merges pixels of a waveform into a coarser one; samples_per_pixel is rounded down to a multiple of the source
+pub fn downsample_peaks(waveform: &WaveformData, samples_per_pixel: u32) -> WaveformData {
+  let factor: usize = (samples_per_pixel / waveform.samples_per_pixel).max(1) as usize;
+  let mut data: Vec<i16> = Vec::with_capacity(waveform.data.len() / factor + 2);
+  for chunk in waveform.data.chunks(2 * factor) {
+    let min: i16 = chunk.iter().step_by(2).copied().min().unwrap_or(0);
+    let max: i16 = chunk.iter().skip(1).step_by(2).copied().max().unwrap_or(0);
+    data.push(min);
+    data.push(max);
+  }
+
+  WaveformData {
+    samples_per_pixel: waveform.samples_per_pixel * factor as u32,
+    length: (data.len() / 2) as u32,
+    data,
+    ..waveform.clone()
+  }
+}
+
+// every cached zoom level, derived from the peaks at the finest one
+pub fn waveform_levels(base: WaveformData) -> WaveformCache {
+  let mut levels: Vec<WaveformData> = vec![base];
+  for samples_per_pixel in WAVEFORM_LEVELS.iter().skip(1) {
+    let coarser = downsample_peaks(levels.last().unwrap(), *samples_per_pixel);
+    levels.push(coarser);
+  }
+  WaveformCache { levels }
+}
+
+// picks the requested resolution from the cache. A width is turned into the samples per pixel that
+// fits the whole clip into that many pixels; resolutions finer than the base level are clamped to it
+pub fn select_waveform(cache: &WaveformCache, width: Option<u32>, samples_per_pixel: Option<u32>) -> Result<WaveformData, String> {
+  let base: &WaveformData = cache.levels.first().ok_or("Waveform cache is empty")?;
+  let total_samples: u64 = base.length as u64 * base.samples_per_pixel as u64;
+
+  let requested: u32 = match (samples_per_pixel, width) {
+    (Some(0), _) | (None, Some(0)) => return Err("Waveform resolution must be greater than zero".to_string()),
+    (Some(samples_per_pixel), _) => samples_per_pixel,
+    (None, Some(width)) => total_samples.div_ceil(width as u64) as u32,
+    (None, None) => return Ok(base.clone()),
+  };
+
+  let level: &WaveformData = cache.levels.iter()
+    .rev()
+    .find(|level| level.samples_per_pixel <= requested)
+    .unwrap_or(base);
+
+  if level.samples_per_pixel == requested || requested < level.samples_per_pixel * 2 {
+    return Ok(level.clone());
+  }
+  Ok(downsample_peaks(level, requested))
+}
+
+pub fn file_stamp(file_path: &str) -> Result<FileStamp, String> {
+  let metadata: std::fs::Metadata = std::fs::metadata(file_path).map_err(|e| format!("Unable to read file: {}", e))?;
+  let modified_ns: u64 = metadata.modified()
+    .ok()
+    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+    .map_or(0, |since| since.as_nanos() as u64);
+  Ok(FileStamp { path: file_path.to_string(), size: metadata.len(), modified_ns })
+}
+
+fn waveform_cache_file(audio_id: i32) -> PathBuf {
+  waveform_cache_path().join(format!("{}.json", audio_id))
+}
+
+// the cached peaks, if the entry was generated from the file as it is now
+pub fn read_cached_waveform(cache_file: &Path, stamp: &FileStamp) -> Option<WaveformCache> {
+  let bytes: Vec<u8> = std::fs::read(cache_file).ok()?;
+  match serde_json::from_slice::<StoredWaveform>(&bytes) {
+    Ok(stored) if stored.stamp == *stamp => Some(stored.cache),
+    Ok(_) => None,
+    // a corrupt cache entry is regenerated
+    Err(err) => {
+      eprintln!("Discarding waveform cache {}: {}", cache_file.display(), err);
+      None
+    }
+  }
+}
+
+pub fn write_cached_waveform(cache_file: &Path, stamp: FileStamp, cache: WaveformCache) -> Result<WaveformCache, String> {
+  let cache_dir: &Path = cache_file.parent().unwrap();
+  std::fs::create_dir_all(cache_dir).map_err(|e| format!("Unable to create waveform cache directory: {}", e))?;
+  let stored = StoredWaveform { stamp, cache };
+  let serialized: Vec<u8> = serde_json::to_vec(&stored).map_err(|e| e.to_string())?;
+  std::fs::write(cache_file, serialized).map_err(|e| format!("Unable to write waveform cache: {}", e))?;
+  Ok(stored.cache)
+}
+
+// loads the cached peaks of an entry, generating them if the entry has none yet or its file changed
+pub async fn ensure_waveform(audio_id: i32, file_path: &str) -> Result<WaveformCache, String> {
+  let stamp: FileStamp = file_stamp(file_path)?;
+  let cache_file: PathBuf = waveform_cache_file(audio_id);
+  if let Some(cache) = read_cached_waveform(&cache_file, &stamp) {
+    return Ok(cache);
+  }
+
+  let base: WaveformData = decode_with_fallback(file_path, |info| Ok(PeakBuilder::new(info, WAVEFORM_LEVELS[0]))).await?;
+  write_cached_waveform(&cache_file, stamp, waveform_levels(base))
+}
+
+#[tauri::command(async)]
+pub async fn get_waveform(audio_id_arg: i32, width: Option<u32>, samples_per_pixel: Option<u32>) -> Result<WaveformData, String> {
+  use crate::schema::audio::dsl::*;
+
+  let selected_audio: Audio = {
+    let mut connection: SqliteConnection = establish_connection();
+    audio.find(audio_id_arg)
+      .first::<Audio>(&mut connection)
+      .map_err(|e| format!("Error finding audio: {}", e))?
+  };
+
+  let cache: WaveformCache = ensure_waveform(selected_audio.id, &selected_audio.path).await?;
+  select_waveform(&cache, width, samples_per_pixel)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use tempfile::tempdir;
+
+  // reference peaks of already decoded mono samples
+  fn compute_peaks(samples: &[i16], sample_rate: u32, samples_per_pixel: u32) -> WaveformData {
+    let mut data: Vec<i16> = Vec::with_capacity(2 * samples.len() / samples_per_pixel as usize + 2);
+    for chunk in samples.chunks(samples_per_pixel as usize) {
+      let min: i16 = chunk.iter().copied().min().unwrap_or(0);
+      let max: i16 = chunk.iter().copied().max().unwrap_or(0);
+      data.push(min);
+      data.push(max);
+    }
+
+    WaveformData {
+      version: 2,
+      channels: 1,
+      sample_rate,
+      samples_per_pixel,
+      bits: 16,
+      length: (data.len() / 2) as u32,
+      data,
+    }
+  }
+
+  fn build_waveform_cache(samples: &[i16], sample_rate: u32) -> WaveformCache {
+    waveform_levels(compute_peaks(samples, sample_rate, WAVEFORM_LEVELS[0]))
+  }
+
+  #[test]
+  fn test_compute_peaks() {
+    let samples: Vec<i16> = vec![1, -2, 3, -4, 5, 6, -7];
+    let waveform = compute_peaks(&samples, 8000, 3);
+    assert_eq!(waveform.length, 3);
+    assert_eq!(waveform.data, vec![-2, 3, -4, 6, -7, -7]);
+    assert_eq!(waveform.samples_per_pixel, 3);
+  }
+
+  #[test]
+  fn test_compute_peaks_empty() {
+    let waveform = compute_peaks(&[], 8000, 256);
+    assert_eq!(waveform.length, 0);
+    assert!(waveform.data.is_empty());
+  }
+
+  #[test]
+  fn test_peak_builder_matches_compute_peaks() {
+    let stereo: Vec<f32> = (0..10_000).map(|i| ((i * 37) % 2000) as f32 / 1000.0 - 1.0).collect();
+    let info = AudioInfo { sample_rate: 8000, channels: 2, total_frames: None };
+    let mut builder = PeakBuilder::new(&info, 256);
+    // chunks that do not line up with the pixels
+    for chunk in stereo.chunks(998) {
+      let _ = builder.push(chunk);
+    }
+
+    let mono: Vec<i16> = stereo.chunks(2)
+      .map(|frame| ((frame[0] + frame[1]) / 2.0 * i16::MAX as f32) as i16)
+      .collect();
+    assert_eq!(builder.finish(&info).unwrap(), compute_peaks(&mono, 8000, 256));
+  }
+
+  #[test]
+  fn test_downsample_peaks_matches_direct_computation() {
+    let samples: Vec<i16> = (0..10_000).map(|i| ((i * 37) % 2000 - 1000) as i16).collect();
+    let fine = compute_peaks(&samples, 8000, 256);
+    let coarse = downsample_peaks(&fine, 1024);
+    assert_eq!(coarse, compute_peaks(&samples, 8000, 1024));
+  }
+
+  #[test]
+  fn test_build_waveform_cache_levels() {
+    let samples: Vec<i16> = vec![0; 100_000];
+    let cache = build_waveform_cache(&samples, 44100);
+    let levels: Vec<u32> = cache.levels.iter().map(|l| l.samples_per_pixel).collect();
+    assert_eq!(levels, WAVEFORM_LEVELS.to_vec());
+  }
+
+  #[test]
+  fn test_cached_waveform_follows_the_file() {
+    let dir = tempdir().unwrap();
+    let audio_path = dir.path().join("audio.wav");
+    let cache_file = dir.path().join("cache").join("1.json");
+    std::fs::write(&audio_path, [0u8; 64]).unwrap();
+    let stamp = file_stamp(audio_path.to_str().unwrap()).unwrap();
+    assert!(read_cached_waveform(&cache_file, &stamp).is_none());
+
+    write_cached_waveform(&cache_file, stamp.clone(), build_waveform_cache(&[0; 1000], 44100)).unwrap();
+    assert_eq!(read_cached_waveform(&cache_file, &stamp).unwrap().levels.len(), WAVEFORM_LEVELS.len());
+
+    // a changed file or another file under the same id misses the cache
+    std::fs::write(&audio_path, [0u8; 128]).unwrap();
+    assert!(read_cached_waveform(&cache_file, &file_stamp(audio_path.to_str().unwrap()).unwrap()).is_none());
+    assert!(read_cached_waveform(&cache_file, &FileStamp { path: "/other.wav".to_string(), ..stamp }).is_none());
+  }
+
+  #[test]
+  fn test_select_waveform_by_width() {
+    let samples: Vec<i16> = vec![0; 256 * 4000];
+    let cache = build_waveform_cache(&samples, 44100);
+
+    let waveform = select_waveform(&cache, Some(500), None).unwrap();
+    assert_eq!(waveform.samples_per_pixel, 2048);
+    assert_eq!(waveform.length, 500);
+  }
+
+  #[test]
+  fn test_select_waveform_clamps_to_base() {
+    let samples: Vec<i16> = vec![0; 256 * 10];
+    let cache = build_waveform_cache(&samples, 44100);
+    let waveform = select_waveform(&cache, None, Some(16)).unwrap();
+    assert_eq!(waveform.samples_per_pixel, 256);
+  }
+
+  #[test]
+  fn test_select_waveform_zero_resolution() {
+    let cache = build_waveform_cache(&[0; 10], 44100);
+    assert!(select_waveform(&cache, Some(0), None).is_err());
+    assert!(select_waveform(&cache, None, Some(0)).is_err());
+  }
+}
@@ -0,0 +1,85 @@
+use diesel::prelude::*;
+use crate::db::establish_connection;
+use crate::helper::artwork::{artwork_uri, store_artwork};
+use crate::helper::files::read_file_to_buffer;
+use crate::helper::tags::read_front_cover;
+use crate::models::audio_model::Audio;
+
+// stores the cover art embedded in the file (if any) and returns its hash
+pub fn extract_artwork_from_file(file_path: &str) -> Result<Option<String>, String> {
+  match read_front_cover(file_path)? {
+    Some(bytes) => Ok(Some(store_artwork(&bytes)?)),
+    None => Ok(None),
+  }
+}
+
+// downloads a remote thumbnail (e.g. from YouTube) into the artwork cache and returns its hash
+pub async fn fetch_artwork_from_url(url: &str) -> Result<String, String> {
+  let response: reqwest::Response = reqwest::get(url).await.map_err(|e| e.to_string())?;
+  if !response.status().is_success() {
+    return Err(format!("Error fetching artwork: {}", response.status()));
+  }
+  let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+  store_artwork(&bytes)
+}
+
+fn link_artwork(audio_id_arg: i32, hash: Option<&str>) -> Result<(), String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::update(audio.find(audio_id_arg))
+    .set(artwork_hash.eq(hash))
+    .execute(&mut connection)
+    .map_err(|e| format!("Error updating audio entry: {}", e))?;
+  Ok(())
+}
+
+#[tauri::command(async)]
+pub fn extract_artwork(audio_id_arg: i32) -> Result<Option<String>, String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let selected_audio: Audio = audio
+    .find(audio_id_arg)
+    .first::<Audio>(&mut connection)
+    .map_err(|e| format!("Error finding audio: {}", e))?;
+
+  let hash: Option<String> = extract_artwork_from_file(&selected_audio.path)?;
+  if hash.is_some() {
+    link_artwork(audio_id_arg, hash.as_deref())?;
+  }
+
+  Ok(hash.map(|hash| artwork_uri(&hash, None)))
+}
+
+#[tauri::command(async)]
+pub fn set_artwork(audio_id_arg: i32, image_path: String) -> Result<String, String> {
+  let bytes: Vec<u8> = read_file_to_buffer(&image_path)?;
+  let hash: String = store_artwork(&bytes)?;
+  link_artwork(audio_id_arg, Some(&hash))?;
+
+  Ok(artwork_uri(&hash, None))
+}
+
+#[tauri::command]
+pub fn remove_artwork(audio_id_arg: i32) -> Result<(), String> {
+  // the cached files are content addressed and may be shared, so only the link is removed
+  link_artwork(audio_id_arg, None)
+}
+
+#[tauri::command]
+pub fn get_artwork_uri(audio_id_arg: i32, size: Option<u32>) -> Result<Option<String>, String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let hash: Option<String> = audio
+    .find(audio_id_arg)
+    .select(artwork_hash)
+    .first::<Option<String>>(&mut connection)
+    .map_err(|e| format!("Error finding audio: {}", e))?;
+
+  Ok(hash.map(|hash| artwork_uri(&hash, size)))
+}
@@ -4,18 +4,25 @@ use lofty::tag::Accessor;
 use lofty::probe::Probe;
 use crate::helper::constants::audio_store_path;
 use crate::helper::tools::seconds_to_minutes;
+use crate::helper::decoder::probe_duration;
 use crate::schema::audio::dsl::*;
 use crate::models::audio_model:: {
-    Audio, AudioArg, NewAudio,
+    Audio, AudioArg, AudioFilter, AudioSort, NewAudio,
 };
 use crate::db::establish_connection;
+use crate::commands::tag_commands::write_audio_tags;
+use crate::commands::artwork_commands::extract_artwork_from_file;
+use crate::commands::analysis::waveform::ensure_waveform;
+use crate::commands::analysis::loudness::analyze_and_store_loudness;
 
 fn read_file_metadata(file_path: String) -> Result<AudioArg, String> {
   use crate::helper::files::{
       get_file_type,
       extract_file_name,
       create_audio_store_directory,
-      copy_file_to_destination
+      copy_file_to_destination,
+      next_available_path,
+      discard_output_path
   };
   let file_name: String = extract_file_name(&file_path)?;
   
@@ -37,28 +44,30 @@ fn read_file_metadata(file_path: String) -> Result<AudioArg, String> {
   };
   let properties = tagged_file.properties();
 
-  let duration_secs = properties.duration().as_secs();
+  // the decoder counts frames exactly; lofty estimates from the header, which drifts for VBR files
+  let duration_secs = match probe_duration(&file_path) {
+    Ok(probed_secs) => probed_secs.round() as u64,
+    Err(_) => properties.duration().as_secs(),
+  };
   let audio_author = if let Some(artist) = tag.artist() {
     artist.to_string()
   } else {
     "Unknown".to_string()
   };
+  let audio_album = tag.album().map(|s| s.to_string());
+  let audio_genre = tag.genre().map(|s| s.to_string());
+  let audio_comment = tag.comment().map(|s| s.to_string());
   
   create_audio_store_directory()?;
 
   // create destination path based on file_name, if there is a duplicate, add a suffix -1, -2, etc.
-  let audio_store_path = audio_store_path();
-  let mut destination_path = audio_store_path.join(&file_name);
-  let mut counter = 1;
-        
-  while destination_path.exists() {
-      // Create a new destination path with a counter suffix
-      let new_file_name = format!("{}-{}", file_name, counter);
-      destination_path = audio_store_path.join(new_file_name);
-      counter += 1;
-  }
+  let source_path = std::path::Path::new(&file_path);
+  let file_stem = source_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(&file_name);
+  let file_extension = source_path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+  let destination_path = next_available_path(&audio_store_path(), file_stem, file_extension)?;
 
-  copy_file_to_destination(&file_path, destination_path.to_str().unwrap()).map_err(|e| format!("Unable to copy file: {}", e))?;
+  copy_file_to_destination(&file_path, destination_path.to_str().unwrap())
+    .map_err(|e| discard_output_path(&destination_path, format!("Unable to copy file: {}", e)))?;
 
   Ok(AudioArg{
       title: Some(file_name),
@@ -73,9 +82,58 @@ fn read_file_metadata(file_path: String) -> Result<AudioArg, String> {
           "audio/m4b" => Some("m4b".to_string()),
           _ => Some("unknown".to_string()),
       },
+      album: audio_album,
+      genre: audio_genre,
+      comment: audio_comment,
+      bpm: None,
+      musical_key: None,
   })
 }
 
+// inserts a new entry and returns the stored row, used by the commands that derive new entries
+pub fn insert_audio_entry(new_audio: &NewAudio) -> Result<Audio, String> {
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::insert_into(audio)
+    .values(new_audio)
+    .get_result::<Audio>(&mut connection)
+    .map_err(|e| format!("Error: Could not add audio entry to database: {}", e))
+}
+
+pub fn find_audio(audio_id_arg: i32) -> Result<Audio, String> {
+  let mut connection: SqliteConnection = establish_connection();
+
+  audio.find(audio_id_arg)
+    .first::<Audio>(&mut connection)
+    .map_err(|e| format!("Error finding audio {}: {}", audio_id_arg, e))
+}
+
+// the entry downloaded from the given video or track of an extractor, if any
+pub fn find_audio_by_source(extractor: &str, video_id: &str) -> Result<Option<Audio>, String> {
+  let mut connection: SqliteConnection = establish_connection();
+
+  // entries holding only a section of the video do not count
+  audio.filter(source_extractor.eq(extractor))
+    .filter(source_video_id.eq(video_id))
+    .filter(source_start.is_null())
+    .filter(source_end.is_null())
+    .first::<Audio>(&mut connection)
+    .optional()
+    .map_err(|e| format!("Error finding audio for {} {}: {}", extractor, video_id, e))
+}
+
+// precomputes the waveform and measures loudness in the background so imports stay fast
+pub fn spawn_import_analysis(audio_id_arg: i32, file_path: String) {
+  tauri::async_runtime::spawn(async move {
+    if let Err(err) = ensure_waveform(audio_id_arg, &file_path).await {
+      eprintln!("Error generating waveform: {}", err);
+    }
+    if let Err(err) = analyze_and_store_loudness(audio_id_arg, &file_path).await {
+      eprintln!("Error measuring loudness: {}", err);
+    }
+  });
+}
+
 #[tauri::command]
 pub fn create_audio(file_path: String) -> Result<(), String> {
   let audio_arg: AudioArg = match read_file_metadata(file_path) {
@@ -83,6 +141,15 @@ pub fn create_audio(file_path: String) -> Result<(), String> {
     Err(err) => return Err(err),
   };
   
+  // embedded cover art is optional, a file without artwork is still imported
+  let artwork: Option<String> = match extract_artwork_from_file(audio_arg.path.as_deref().unwrap_or_default()) {
+    Ok(hash) => hash,
+    Err(err) => {
+      eprintln!("Error extracting artwork: {}", err);
+      None
+    }
+  };
+
   let mut connection: SqliteConnection = establish_connection();
 
   let new_audio: NewAudio<'_> = NewAudio{
@@ -90,15 +157,24 @@ pub fn create_audio(file_path: String) -> Result<(), String> {
     author: &audio_arg.author.unwrap_or_default().to_string(),
     path: &audio_arg.path.unwrap_or_default().to_string(),
     duration: &audio_arg.duration.unwrap_or_default().to_string(),
-    audio_type: &audio_arg.audio_type.unwrap_or_default().to_string()
+    audio_type: &audio_arg.audio_type.unwrap_or_default().to_string(),
+    album: audio_arg.album.as_deref(),
+    genre: audio_arg.genre.as_deref(),
+    comment: audio_arg.comment.as_deref(),
+    artwork_hash: artwork.as_deref(),
+    ..Default::default()
   };
 
-  let result: Result<usize, diesel::result::Error> = diesel::insert_into(audio)
+  let result: Result<i32, diesel::result::Error> = diesel::insert_into(audio)
     .values(&new_audio)
-    .execute(&mut connection);
+    .returning(id)
+    .get_result(&mut connection);
 
   match result {
-    Ok(_) => Ok(()),
+    Ok(new_id) => {
+      spawn_import_analysis(new_id, new_audio.path.to_string());
+      Ok(())
+    }
 
     Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => {
         Err("Error: Could not add audio entry to database".to_string()) // Return error to the client
@@ -146,7 +222,62 @@ pub fn get_audio(audio_id_arg: i32) -> Result<Vec<Audio>, String> {
 }
 
 #[tauri::command]
-pub fn update_audio(id_arg: i32, audio_arg: AudioArg) -> Result<(), String> {
+pub fn search_audio(filter: AudioFilter) -> Result<Vec<Audio>, String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let mut query = audio.into_boxed();
+  if let Some(search_text) = filter.query.filter(|q| !q.trim().is_empty()) {
+    let pattern: String = format!("%{}%", search_text.trim());
+    query = query.filter(title.like(pattern.clone()).or(author.like(pattern)));
+  }
+  if let Some(min) = filter.min_integrated_lufs {
+    query = query.filter(integrated_lufs.ge(min));
+  }
+  if let Some(max) = filter.max_integrated_lufs {
+    query = query.filter(integrated_lufs.le(max));
+  }
+  if let Some(min) = filter.min_loudness_range {
+    query = query.filter(loudness_range.ge(min));
+  }
+  if let Some(max) = filter.max_loudness_range {
+    query = query.filter(loudness_range.le(max));
+  }
+  if let Some(max) = filter.max_true_peak {
+    query = query.filter(true_peak.le(max));
+  }
+  if let Some(min) = filter.min_bpm {
+    query = query.filter(bpm.ge(min));
+  }
+  if let Some(max) = filter.max_bpm {
+    query = query.filter(bpm.le(max));
+  }
+  if let Some(key) = filter.musical_key {
+    query = query.filter(musical_key.eq(key));
+  }
+
+  let descending: bool = filter.descending.unwrap_or(false);
+  query = match (filter.sort_by, descending) {
+    (None, _) => query,
+    (Some(AudioSort::Title), false) => query.order(title.asc()),
+    (Some(AudioSort::Title), true) => query.order(title.desc()),
+    (Some(AudioSort::Author), false) => query.order(author.asc()),
+    (Some(AudioSort::Author), true) => query.order(author.desc()),
+    (Some(AudioSort::Bpm), false) => query.order(bpm.asc()),
+    (Some(AudioSort::Bpm), true) => query.order(bpm.desc()),
+    (Some(AudioSort::MusicalKey), false) => query.order(musical_key.asc()),
+    (Some(AudioSort::MusicalKey), true) => query.order(musical_key.desc()),
+    (Some(AudioSort::IntegratedLufs), false) => query.order(integrated_lufs.asc()),
+    (Some(AudioSort::IntegratedLufs), true) => query.order(integrated_lufs.desc()),
+  };
+
+  query.load::<Audio>(&mut connection)
+    .map_err(|e| format!("Error querying audio: {}", e))
+}
+
+#[tauri::command]
+pub fn update_audio(id_arg: i32, audio_arg: AudioArg, write_back: Option<bool>) -> Result<(), String> {
   use crate::schema::audio::dsl::*;
 
   let mut connection: SqliteConnection = establish_connection();
@@ -162,17 +293,30 @@ pub fn update_audio(id_arg: i32, audio_arg: AudioArg) -> Result<(), String> {
         author: audio_arg.author.unwrap_or(current_audio.author),
         path: audio_arg.path.unwrap_or(current_audio.path),
         duration: audio_arg.duration.unwrap_or(current_audio.duration),
-        audio_type: audio_arg.audio_type.unwrap_or(current_audio.audio_type)
+        audio_type: audio_arg.audio_type.unwrap_or(current_audio.audio_type),
+        album: audio_arg.album.or(current_audio.album),
+        genre: audio_arg.genre.or(current_audio.genre),
+        comment: audio_arg.comment.or(current_audio.comment),
+        // manual tempo/key overrides win over the analysis, which skips entries that already have a tempo
+        bpm: audio_arg.bpm.or(current_audio.bpm),
+        musical_key: audio_arg.musical_key.or(current_audio.musical_key),
+        ..current_audio
     };
  
     let result: Result<_, _> = diesel::update(audio.find(id_arg))
       .set(&new_audio)
       .execute(&mut connection);
 
-    match result {
-      Ok(_) => Ok(()),
-      Err(err) => Err(format!("Error updating audio entry: {}", err)), // Return error to the client
+    if let Err(err) = result {
+      return Err(format!("Error updating audio entry: {}", err)); // Return error to the client
     }
+
+    // optionally keep the file in the audio store in sync with the catalog
+    if write_back.unwrap_or(false) {
+      write_audio_tags(&new_audio, None)?;
+    }
+
+    Ok(())
 }
 
 #[tauri::command]
@@ -182,8 +326,8 @@ pub fn delete_audio(audio_id_arg: i32) -> Result<(), String> {
   
   let mut connection: SqliteConnection = establish_connection();
 
-  // Delete playlist entries from playlist_audio first to maintain referential integrity
-  let result_playlist_audio: Result<usize, diesel::result::Error> = diesel::delete(playlist_audio.filter(playlist_id.eq(audio_id_arg)))
+  // Delete the entry's playlist memberships first to maintain referential integrity
+  let result_playlist_audio: Result<usize, diesel::result::Error> = diesel::delete(playlist_audio.filter(audio_id.eq(audio_id_arg)))
     .execute(&mut connection);
 
   match result_playlist_audio {
@@ -191,13 +335,13 @@ pub fn delete_audio(audio_id_arg: i32) -> Result<(), String> {
     Err(err) => return Err(format!("Error deleting playlist audio entries: {}", err)), // Return error to the client
   }
 
-  // Delete the playlist entry
-  let result_playlist: Result<usize, diesel::result::Error> = diesel::delete(audio.filter(id.eq(audio_id_arg)))
+  // Delete the audio entry
+  let result_audio: Result<usize, diesel::result::Error> = diesel::delete(audio.filter(id.eq(audio_id_arg)))
     .execute(&mut connection);
 
-  match result_playlist {
+  match result_audio {
     Ok(_) => Ok(()),
-    Err(err) => Err(format!("Error deleting playlist entry: {}", err)), // Return error to the client
+    Err(err) => Err(format!("Error deleting audio entry: {}", err)), // Return error to the client
   }
 }
 
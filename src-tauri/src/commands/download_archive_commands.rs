@@ -0,0 +1,65 @@
+use diesel::prelude::*;
+use crate::models::download_archive_model::{ArchiveEntry, NewArchiveEntry};
+use crate::db::establish_connection;
+
+pub fn find_archive_entry(extractor_arg: &str, video_id_arg: &str) -> Result<Option<ArchiveEntry>, String> {
+  use crate::schema::download_archive::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  download_archive
+    .filter(extractor.eq(extractor_arg))
+    .filter(video_id.eq(video_id_arg))
+    .first::<ArchiveEntry>(&mut connection)
+    .optional()
+    .map_err(|e| format!("Error querying download archive: {}", e))
+}
+
+// a forced re-download replaces the earlier record
+pub fn record_download(new_entry: &NewArchiveEntry) -> Result<(), String> {
+  use crate::schema::download_archive::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::replace_into(download_archive)
+    .values(new_entry)
+    .execute(&mut connection)
+    .map_err(|e| format!("Error recording download: {}", e))?;
+  Ok(())
+}
+
+#[tauri::command]
+pub fn get_download_archive() -> Result<Vec<ArchiveEntry>, String> {
+  use crate::schema::download_archive::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  download_archive.order(downloaded_on.desc())
+    .load::<ArchiveEntry>(&mut connection)
+    .map_err(|e| format!("Error querying download archive: {}", e))
+}
+
+#[tauri::command]
+pub fn delete_download_archive_entry(id_arg: i32) -> Result<(), String> {
+  use crate::schema::download_archive::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::delete(download_archive.find(id_arg))
+    .execute(&mut connection)
+    .map_err(|e| format!("Error deleting download archive entry: {}", e))?;
+  Ok(())
+}
+
+// forgets every download so each video can be fetched again
+#[tauri::command]
+pub fn clear_download_archive() -> Result<(), String> {
+  use crate::schema::download_archive::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::delete(download_archive)
+    .execute(&mut connection)
+    .map_err(|e| format!("Error clearing download archive: {}", e))?;
+  Ok(())
+}
@@ -31,6 +31,18 @@ pub fn create_playlist(playlist_arg: PlaylistArg) -> Result<(), String> {
   }
 }
 
+// inserts a new playlist and returns the stored row, used by the commands that fill a playlist they create
+pub fn insert_playlist_entry(new_playlist: &NewPlaylist) -> Result<Playlist, String> {
+  use crate::schema::playlist::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::insert_into(playlist)
+    .values(new_playlist)
+    .get_result::<Playlist>(&mut connection)
+    .map_err(|e| format!("Error: Could not create playlist entry: {}", e))
+}
+
 #[tauri::command]
 pub fn get_all_playlists() -> Result<Vec<Playlist>, String> {
   use crate::schema::playlist::dsl::*;
@@ -126,6 +138,20 @@ pub fn get_all_audio_from_playlist(playlist_id_arg: i32) -> Result<Vec<Audio>, S
   Ok(audio_list)
 }
 
+// ids of the playlist entries in the order they were added
+pub fn get_playlist_audio_ids(playlist_id_arg: i32) -> Result<Vec<i32>, String> {
+  use crate::schema::playlist_audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  playlist_audio
+    .filter(playlist_id.eq(playlist_id_arg))
+    .select(audio_id)
+    .order(diesel::dsl::sql::<diesel::sql_types::Integer>("rowid"))
+    .load::<i32>(&mut connection)
+    .map_err(|e| format!("Error querying playlist audio entries: {}", e))
+}
+
 #[tauri::command]
 pub fn delete_playlist(playlist_id_arg: i32) -> Result<(), String> {
   use crate::schema::playlist::dsl::*;
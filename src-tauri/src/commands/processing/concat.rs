@@ -0,0 +1,206 @@
+use std::path::{Path, PathBuf};
+use crate::commands::audio_commands::{find_audio, insert_audio_entry, spawn_import_analysis};
+use crate::commands::playlist_commands::{get_playlist, get_playlist_audio_ids};
+use crate::commands::processing::normalize::audio_file_extension;
+use crate::helper::constants::audio_store_path;
+use crate::helper::decoder::{probe_audio, probe_duration, AudioInfo};
+use crate::helper::files::{create_audio_store_directory, discard_output_path, next_available_path, trim_invalid_file_characters};
+use crate::helper::sidecar::run_ffmpeg;
+use crate::helper::tools::seconds_to_minutes;
+use crate::models::audio_model::{Audio, NewAudio};
+use crate::models::playlist_model::Playlist;
+
+// stream parameters of one clip of the mix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConcatInput {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub duration: f64,
+}
+
+// every clip is converted to the highest sample rate and channel count among them
+pub fn common_format(inputs: &[ConcatInput]) -> (u32, u16) {
+    let sample_rate: u32 = inputs.iter().map(|input| input.sample_rate).max().unwrap_or(44100);
+    let channels: u16 = inputs.iter().map(|input| input.channels).max().unwrap_or(2);
+    (sample_rate, channels)
+}
+
+fn channel_layout(channels: u16) -> String {
+    match channels {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        n => format!("{}c", n),
+    }
+}
+
+// filter_complex joining the inputs in order, either crossfading (seconds) or with silence in between
+pub fn concat_filter_graph(inputs: &[ConcatInput], crossfade: f64, gap: f64) -> Result<String, String> {
+    if inputs.len() < 2 {
+        return Err("At least two clips are needed".to_string());
+    }
+    if crossfade > 0.0 && gap > 0.0 {
+        return Err("Crossfades and gaps cannot be combined".to_string());
+    }
+    if let Some(short) = inputs.iter().find(|input| input.duration <= crossfade) {
+        return Err(format!("Crossfade is longer than a {:.3} s clip", short.duration));
+    }
+
+    let (sample_rate, channels) = common_format(inputs);
+    let mut graph: Vec<String> = Vec::with_capacity(inputs.len() * 2);
+    for index in 0..inputs.len() {
+        let pad: String = if gap > 0.0 && index < inputs.len() - 1 { format!(",apad=pad_dur={:.3}", gap) } else { String::new() };
+        graph.push(format!(
+            "[{}:a]aresample={},aformat=sample_fmts=fltp:channel_layouts={}{}[a{}]",
+            index, sample_rate, channel_layout(channels), pad, index
+        ));
+    }
+
+    if crossfade > 0.0 {
+        let mut previous: String = "a0".to_string();
+        for index in 1..inputs.len() {
+            let label: String = if index == inputs.len() - 1 { "out".to_string() } else { format!("x{}", index) };
+            graph.push(format!("[{}][a{}]acrossfade=d={:.3}[{}]", previous, index, crossfade, label));
+            previous = label;
+        }
+    } else {
+        let labels: String = (0..inputs.len()).map(|index| format!("[a{}]", index)).collect();
+        graph.push(format!("{}concat=n={}:v=0:a=1[out]", labels, inputs.len()));
+    }
+
+    Ok(graph.join(";"))
+}
+
+// expected length of the mix in seconds
+pub fn concat_duration(inputs: &[ConcatInput], crossfade: f64, gap: f64) -> f64 {
+    let joins: f64 = inputs.len().saturating_sub(1) as f64;
+    inputs.iter().map(|input| input.duration).sum::<f64>() - crossfade * joins + gap * joins
+}
+
+async fn render_concat(sources: &[Audio], graph: &str, destination: &Path) -> Result<(), String> {
+    let mut args: Vec<String> = vec!["-hide_banner".to_string()];
+    for source in sources {
+        args.push("-i".to_string());
+        args.push(source.path.clone());
+    }
+    args.extend([
+        "-filter_complex".to_string(), graph.to_string(),
+        "-map".to_string(), "[out]".to_string(),
+        "-y".to_string(),
+        destination.to_str().unwrap().to_string(),
+    ]);
+
+    run_ffmpeg(&args).await?;
+    Ok(())
+}
+
+// joins the entries in the given order into a single new entry titled `title`
+pub async fn concat_entries(sources: Vec<Audio>, title: &str, crossfade_ms: Option<u32>, gap_ms: Option<u32>, output_format: Option<String>) -> Result<Audio, String> {
+    let mut inputs: Vec<ConcatInput> = Vec::with_capacity(sources.len());
+    for source in &sources {
+        let info: AudioInfo = probe_audio(&source.path)?;
+        inputs.push(ConcatInput {
+            sample_rate: info.sample_rate,
+            channels: info.channels,
+            duration: info.duration_secs().ok_or(format!("Unable to determine duration of {}", source.title))?,
+        });
+    }
+
+    let crossfade: f64 = crossfade_ms.unwrap_or(0) as f64 / 1000.0;
+    let gap: f64 = gap_ms.unwrap_or(0) as f64 / 1000.0;
+    let graph: String = concat_filter_graph(&inputs, crossfade, gap)?;
+
+    let extension: String = match output_format {
+        Some(format) if !format.is_empty() && format.chars().all(|c| c.is_ascii_alphanumeric()) => format.to_lowercase(),
+        Some(format) => return Err(format!("Invalid output format {}", format)),
+        None => audio_file_extension(&sources[0]),
+    };
+
+    create_audio_store_directory()?;
+    let base_file_name: String = trim_invalid_file_characters(title);
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), &base_file_name, &extension)?;
+    render_concat(&sources, &graph, &destination_path).await
+        .map_err(|e| discard_output_path(&destination_path, e))?;
+
+    let output_duration: f64 = probe_duration(destination_path.to_str().unwrap()).unwrap_or(concat_duration(&inputs, crossfade, gap));
+    let created: Audio = insert_audio_entry(&NewAudio {
+        title: &base_file_name,
+        author: &sources[0].author,
+        path: destination_path.to_str().unwrap(),
+        duration: &seconds_to_minutes(output_duration.round() as u64),
+        audio_type: &extension,
+        ..Default::default()
+    })?;
+    spawn_import_analysis(created.id, created.path.clone());
+
+    Ok(created)
+}
+
+#[tauri::command(async)]
+pub async fn concat_audio(audio_ids: Vec<i32>, crossfade_ms: Option<u32>, gap_ms: Option<u32>, output_format: Option<String>) -> Result<Audio, String> {
+    let sources: Vec<Audio> = audio_ids.iter().map(|audio_id| find_audio(*audio_id)).collect::<Result<_, _>>()?;
+    let title: String = format!("mix-of-{}-clips", sources.len());
+    concat_entries(sources, &title, crossfade_ms, gap_ms, output_format).await
+}
+
+// renders the playlist in the order its entries were added as one continuous mix
+#[tauri::command(async)]
+pub async fn render_playlist_mix(playlist_id: i32, crossfade_ms: Option<u32>, gap_ms: Option<u32>, output_format: Option<String>) -> Result<Audio, String> {
+    let mix_playlist: Playlist = get_playlist(playlist_id)?
+        .into_iter()
+        .next()
+        .ok_or(format!("Error finding playlist {}", playlist_id))?;
+
+    let sources: Vec<Audio> = get_playlist_audio_ids(playlist_id)?
+        .into_iter()
+        .map(find_audio)
+        .collect::<Result<_, _>>()?;
+    concat_entries(sources, &format!("{}-mix", mix_playlist.title), crossfade_ms, gap_ms, output_format).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(sample_rate: u32, channels: u16, duration: f64) -> ConcatInput {
+        ConcatInput { sample_rate, channels, duration }
+    }
+
+    #[test]
+    fn test_common_format() {
+        assert_eq!(common_format(&[input(44100, 1, 5.0), input(48000, 2, 5.0), input(22050, 1, 5.0)]), (48000, 2));
+    }
+
+    #[test]
+    fn test_concat_filter_graph() {
+        let graph = concat_filter_graph(&[input(44100, 2, 5.0), input(48000, 1, 5.0)], 0.0, 0.0).unwrap();
+        assert_eq!(
+            graph,
+            "[0:a]aresample=48000,aformat=sample_fmts=fltp:channel_layouts=stereo[a0];\
+             [1:a]aresample=48000,aformat=sample_fmts=fltp:channel_layouts=stereo[a1];\
+             [a0][a1]concat=n=2:v=0:a=1[out]"
+        );
+    }
+
+    #[test]
+    fn test_concat_filter_graph_gap() {
+        let graph = concat_filter_graph(&[input(44100, 1, 5.0), input(44100, 1, 5.0)], 0.0, 1.5).unwrap();
+        assert!(graph.starts_with("[0:a]aresample=44100,aformat=sample_fmts=fltp:channel_layouts=mono,apad=pad_dur=1.500[a0];"));
+        // no trailing silence after the last clip
+        assert!(graph.contains("channel_layouts=mono[a1];"));
+    }
+
+    #[test]
+    fn test_concat_filter_graph_crossfade() {
+        let inputs = [input(44100, 2, 5.0), input(44100, 2, 5.0), input(44100, 2, 5.0)];
+        let graph = concat_filter_graph(&inputs, 0.5, 0.0).unwrap();
+        assert!(graph.ends_with("[a0][a1]acrossfade=d=0.500[x1];[x1][a2]acrossfade=d=0.500[out]"), "{}", graph);
+        assert_eq!(concat_duration(&inputs, 0.5, 0.0), 14.0);
+    }
+
+    #[test]
+    fn test_concat_filter_graph_invalid() {
+        assert!(concat_filter_graph(&[input(44100, 2, 5.0)], 0.0, 0.0).is_err());
+        assert!(concat_filter_graph(&[input(44100, 2, 5.0), input(44100, 2, 0.4)], 0.5, 0.0).is_err());
+        assert!(concat_filter_graph(&[input(44100, 2, 5.0), input(44100, 2, 5.0)], 0.5, 1.0).is_err());
+    }
+}
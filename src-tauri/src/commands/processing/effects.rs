@@ -0,0 +1,247 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::commands::audio_commands::{find_audio, insert_audio_entry, spawn_import_analysis};
+use crate::commands::processing::normalize::audio_file_extension;
+use crate::helper::constants::audio_store_path;
+use crate::helper::decoder::{probe_audio, probe_duration, AudioInfo};
+use crate::helper::files::{create_audio_store_directory, discard_output_path, next_available_path, trim_invalid_file_characters};
+use crate::helper::sidecar::run_ffmpeg;
+use crate::helper::tools::seconds_to_minutes;
+use crate::models::audio_model::{Audio, NewAudio};
+
+// range of a single atempo instance, larger changes are chained
+const ATEMPO_MIN: f64 = 0.5;
+const ATEMPO_MAX: f64 = 2.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AudioEffect {
+    // fade lengths in seconds
+    Fade { fade_in: Option<f64>, fade_out: Option<f64> },
+    Gain { db: f64 },
+    Reverse,
+    // tempo change that keeps the pitch, 2.0 plays twice as fast
+    Speed { factor: f64 },
+    // pitch change that keeps the tempo
+    Pitch { semitones: f64 },
+}
+
+// afade filter chain for a clip of `duration` seconds whose timestamps start at zero
+pub fn fade_filter(duration: f64, fade_in: Option<f64>, fade_out: Option<f64>) -> Option<String> {
+    let mut filters: Vec<String> = Vec::new();
+    if let Some(fade_in) = fade_in.filter(|d| *d > 0.0) {
+        filters.push(format!("afade=t=in:st=0:d={:.3}", fade_in));
+    }
+    if let Some(fade_out) = fade_out.filter(|d| *d > 0.0) {
+        filters.push(format!("afade=t=out:st={:.3}:d={:.3}", (duration - fade_out).max(0.0), fade_out));
+    }
+    if filters.is_empty() { None } else { Some(filters.join(",")) }
+}
+
+// splits a tempo factor into atempo instances that each stay within the supported range
+pub fn atempo_chain(factor: f64) -> String {
+    let mut remaining: f64 = factor;
+    let mut filters: Vec<String> = Vec::new();
+    while remaining > ATEMPO_MAX {
+        filters.push(format!("atempo={}", ATEMPO_MAX));
+        remaining /= ATEMPO_MAX;
+    }
+    while remaining < ATEMPO_MIN {
+        filters.push(format!("atempo={}", ATEMPO_MIN));
+        remaining /= ATEMPO_MIN;
+    }
+    filters.push(format!("atempo={:.6}", remaining));
+    filters.join(",")
+}
+
+impl AudioEffect {
+    pub fn validate(&self) -> Result<(), String> {
+        let valid: bool = match self {
+            AudioEffect::Fade { fade_in, fade_out } => {
+                fade_in.is_none_or(|d| d.is_finite() && d >= 0.0) && fade_out.is_none_or(|d| d.is_finite() && d >= 0.0)
+            }
+            AudioEffect::Gain { db } => db.is_finite(),
+            AudioEffect::Reverse => true,
+            AudioEffect::Speed { factor } => factor.is_finite() && (0.1..=10.0).contains(factor),
+            AudioEffect::Pitch { semitones } => semitones.is_finite() && semitones.abs() <= 24.0,
+        };
+        if valid { Ok(()) } else { Err(format!("Invalid effect parameters: {:?}", self)) }
+    }
+
+    // ffmpeg audio filter for a source with the given stream parameters
+    pub fn filter(&self, info: &AudioInfo) -> Result<String, String> {
+        self.validate()?;
+        match self {
+            AudioEffect::Fade { fade_in, fade_out } => {
+                let duration: f64 = info.duration_secs().ok_or("Unable to determine duration")?;
+                fade_filter(duration, *fade_in, *fade_out).ok_or("No fade length given".to_string())
+            }
+            AudioEffect::Gain { db } => Ok(format!("volume={:.2}dB", db)),
+            AudioEffect::Reverse => Ok("areverse".to_string()),
+            AudioEffect::Speed { factor } => Ok(atempo_chain(*factor)),
+            AudioEffect::Pitch { semitones } => {
+                // play at a different rate, resample back and undo the tempo change
+                let ratio: f64 = 2f64.powf(semitones / 12.0);
+                Ok(format!(
+                    "asetrate={},aresample={},{}",
+                    (info.sample_rate as f64 * ratio).round() as u64,
+                    info.sample_rate,
+                    atempo_chain(1.0 / ratio)
+                ))
+            }
+        }
+    }
+
+    // appended to the title of the new entry
+    pub fn suffix(&self) -> String {
+        match self {
+            AudioEffect::Fade { .. } => "faded".to_string(),
+            AudioEffect::Gain { db } => format!("gain-{:+}dB", db),
+            AudioEffect::Reverse => "reversed".to_string(),
+            AudioEffect::Speed { factor } => format!("speed-{}x", factor),
+            AudioEffect::Pitch { semitones } => format!("pitch-{:+}st", semitones),
+        }
+    }
+}
+
+pub async fn render_filtered(source_path: &str, filter: &str, destination: &Path) -> Result<(), String> {
+    let args: Vec<String> = vec![
+        "-hide_banner".to_string(),
+        "-i".to_string(), source_path.to_string(),
+        "-af".to_string(), filter.to_string(),
+        "-c:v".to_string(), "copy".to_string(),
+        "-map_metadata".to_string(), "0".to_string(),
+        "-y".to_string(),
+        destination.to_str().unwrap().to_string(),
+    ];
+
+    run_ffmpeg(&args).await?;
+    Ok(())
+}
+
+// renders the effect into a new entry linked to the source
+pub async fn apply_effect(audio_id: i32, effect: AudioEffect) -> Result<Audio, String> {
+    let source: Audio = find_audio(audio_id)?;
+    let info: AudioInfo = probe_audio(&source.path)?;
+    let filter: String = effect.filter(&info)?;
+
+    create_audio_store_directory()?;
+    let base_file_name: String = format!("{}-{}", trim_invalid_file_characters(&source.title), effect.suffix());
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), &base_file_name, &audio_file_extension(&source))?;
+    render_filtered(&source.path, &filter, &destination_path).await
+        .map_err(|e| discard_output_path(&destination_path, e))?;
+
+    // speed and pitch changes alter the length, measure the result
+    let output_duration: String = match probe_duration(destination_path.to_str().unwrap()) {
+        Ok(seconds) => seconds_to_minutes(seconds.round() as u64),
+        Err(_) => source.duration.clone(),
+    };
+
+    let created: Audio = insert_audio_entry(&NewAudio {
+        title: &base_file_name,
+        author: &source.author,
+        path: destination_path.to_str().unwrap(),
+        duration: &output_duration,
+        audio_type: &source.audio_type,
+        album: source.album.as_deref(),
+        genre: source.genre.as_deref(),
+        comment: source.comment.as_deref(),
+        artwork_hash: source.artwork_hash.as_deref(),
+        source_audio_id: Some(source.id),
+        ..Default::default()
+    })?;
+    spawn_import_analysis(created.id, created.path.clone());
+
+    Ok(created)
+}
+
+#[tauri::command(async)]
+pub async fn fade_audio(audio_id: i32, fade_in: Option<f64>, fade_out: Option<f64>) -> Result<Audio, String> {
+    apply_effect(audio_id, AudioEffect::Fade { fade_in, fade_out }).await
+}
+
+#[tauri::command(async)]
+pub async fn adjust_gain(audio_id: i32, db: f64) -> Result<Audio, String> {
+    apply_effect(audio_id, AudioEffect::Gain { db }).await
+}
+
+#[tauri::command(async)]
+pub async fn reverse_audio(audio_id: i32) -> Result<Audio, String> {
+    apply_effect(audio_id, AudioEffect::Reverse).await
+}
+
+#[tauri::command(async)]
+pub async fn change_speed(audio_id: i32, factor: f64) -> Result<Audio, String> {
+    apply_effect(audio_id, AudioEffect::Speed { factor }).await
+}
+
+#[tauri::command(async)]
+pub async fn shift_pitch(audio_id: i32, semitones: f64) -> Result<Audio, String> {
+    apply_effect(audio_id, AudioEffect::Pitch { semitones }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(sample_rate: u32, seconds: f64) -> AudioInfo {
+        AudioInfo { sample_rate, channels: 2, total_frames: Some((sample_rate as f64 * seconds) as u64) }
+    }
+
+    #[test]
+    fn test_fade_filter() {
+        assert_eq!(fade_filter(10.0, None, None), None);
+        assert_eq!(fade_filter(10.0, Some(0.0), None), None);
+        assert_eq!(fade_filter(10.0, Some(1.5), None), Some("afade=t=in:st=0:d=1.500".to_string()));
+        assert_eq!(
+            fade_filter(9.5, Some(1.0), Some(2.0)),
+            Some("afade=t=in:st=0:d=1.000,afade=t=out:st=7.500:d=2.000".to_string())
+        );
+    }
+
+    #[test]
+    fn test_atempo_chain() {
+        assert_eq!(atempo_chain(1.25), "atempo=1.250000");
+        assert_eq!(atempo_chain(3.0), "atempo=2,atempo=1.500000");
+        assert_eq!(atempo_chain(0.2), "atempo=0.5,atempo=0.5,atempo=0.800000");
+    }
+
+    #[test]
+    fn test_effect_filters() {
+        let info = info(44100, 10.0);
+        assert_eq!(AudioEffect::Gain { db: -3.5 }.filter(&info).unwrap(), "volume=-3.50dB");
+        assert_eq!(AudioEffect::Reverse.filter(&info).unwrap(), "areverse");
+        assert_eq!(
+            AudioEffect::Fade { fade_in: None, fade_out: Some(2.0) }.filter(&info).unwrap(),
+            "afade=t=out:st=8.000:d=2.000"
+        );
+        // an octave up doubles the rate and halves the tempo back
+        assert_eq!(
+            AudioEffect::Pitch { semitones: 12.0 }.filter(&info).unwrap(),
+            "asetrate=88200,aresample=44100,atempo=0.500000"
+        );
+    }
+
+    #[test]
+    fn test_effect_validation() {
+        let info = info(44100, 10.0);
+        assert!(AudioEffect::Fade { fade_in: None, fade_out: None }.filter(&info).is_err());
+        assert!(AudioEffect::Fade { fade_in: Some(-1.0), fade_out: None }.validate().is_err());
+        assert!(AudioEffect::Gain { db: f64::NAN }.validate().is_err());
+        assert!(AudioEffect::Speed { factor: 0.0 }.validate().is_err());
+        assert!(AudioEffect::Pitch { semitones: 48.0 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_effect_suffix() {
+        assert_eq!(AudioEffect::Gain { db: 3.0 }.suffix(), "gain-+3dB");
+        assert_eq!(AudioEffect::Pitch { semitones: -2.0 }.suffix(), "pitch--2st");
+        assert_eq!(AudioEffect::Speed { factor: 1.5 }.suffix(), "speed-1.5x");
+    }
+
+    #[test]
+    fn test_effect_deserialize() {
+        let effect: AudioEffect = serde_json::from_str(r#"{"type":"speed","factor":1.25}"#).unwrap();
+        assert_eq!(effect, AudioEffect::Speed { factor: 1.25 });
+    }
+}
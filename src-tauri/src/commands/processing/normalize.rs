@@ -0,0 +1,353 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::commands::analysis::loudness::{analyze_and_store_loudness, measure_file_loudness, LoudnessMeasurement};
+use crate::commands::audio_commands::{find_audio, insert_audio_entry};
+use crate::helper::constants::audio_store_path;
+use crate::helper::decoder::probe_audio;
+use crate::helper::files::{create_audio_store_directory, discard_output_path, next_available_path, trim_invalid_file_characters};
+use crate::helper::sidecar::run_ffmpeg;
+use crate::models::audio_model::{Audio, NewAudio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationPreset {
+    // music streaming services (Spotify, YouTube, Apple Music)
+    Streaming,
+    // EBU R128 broadcast delivery
+    Broadcast,
+    Podcast,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoudnessTarget {
+    pub integrated_lufs: f64,
+    pub true_peak: f64,
+    pub loudness_range: f64,
+}
+
+impl NormalizationPreset {
+    pub fn target(&self) -> LoudnessTarget {
+        match self {
+            NormalizationPreset::Streaming => LoudnessTarget { integrated_lufs: -14.0, true_peak: -1.0, loudness_range: 11.0 },
+            NormalizationPreset::Broadcast => LoudnessTarget { integrated_lufs: -23.0, true_peak: -1.0, loudness_range: 20.0 },
+            NormalizationPreset::Podcast => LoudnessTarget { integrated_lufs: -16.0, true_peak: -1.5, loudness_range: 11.0 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizeMode {
+    // ffmpeg loudnorm with a measuring pass followed by a linear correction pass
+    TwoPass,
+    // a single static gain computed from the stored loudness measurement, capped by the true peak target
+    Gain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NormalizeOptions {
+    pub preset: Option<NormalizationPreset>,
+    // overrides the preset when set
+    pub target: Option<LoudnessTarget>,
+    pub mode: Option<NormalizeMode>,
+    // replace the file of the entry instead of creating a new entry, keeping a backup of the original
+    pub in_place: Option<bool>,
+}
+
+impl NormalizeOptions {
+    pub fn resolved_target(&self) -> LoudnessTarget {
+        self.target.unwrap_or_else(|| self.preset.unwrap_or(NormalizationPreset::Streaming).target())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NormalizeResult {
+    pub audio_id: i32,
+    // the new entry, or the same entry when normalized in place
+    pub output_audio_id: Option<i32>,
+    pub output_path: Option<String>,
+    pub backup_path: Option<String>,
+    pub error: Option<String>,
+}
+
+// measurements printed by the first loudnorm pass
+#[derive(Debug, PartialEq)]
+pub struct LoudnormStats {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+pub fn parse_loudnorm_stats(stderr: &str) -> Result<LoudnormStats, String> {
+    let start: usize = stderr.rfind('{').ok_or("loudnorm did not report measurements")?;
+    let end: usize = stderr[start..].find('}').ok_or("loudnorm did not report measurements")? + start;
+    let json: serde_json::Value = serde_json::from_str(&stderr[start..=end])
+        .map_err(|e| format!("Unable to parse loudnorm measurements: {}", e))?;
+
+    let field = |name: &str| -> Result<f64, String> {
+        json.get(name)
+            .and_then(|value| value.as_str())
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|value| value.is_finite())
+            .ok_or(format!("Invalid loudnorm measurement {}", name))
+    };
+
+    Ok(LoudnormStats {
+        input_i: field("input_i")?,
+        input_tp: field("input_tp")?,
+        input_lra: field("input_lra")?,
+        input_thresh: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+    })
+}
+
+pub fn loudnorm_filter(target: &LoudnessTarget, measured: Option<&LoudnormStats>) -> String {
+    let base: String = format!("loudnorm=I={}:TP={}:LRA={}", target.integrated_lufs, target.true_peak, target.loudness_range);
+    match measured {
+        Some(stats) => format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            base, stats.input_i, stats.input_tp, stats.input_lra, stats.input_thresh, stats.target_offset
+        ),
+        None => format!("{}:print_format=json", base),
+    }
+}
+
+// static gain that reaches the loudness target without pushing the true peak over its target
+pub fn normalization_gain_db(measured: &LoudnessMeasurement, target: &LoudnessTarget) -> f64 {
+    let loudness_gain: f64 = target.integrated_lufs - measured.integrated_lufs;
+    let peak_headroom: f64 = target.true_peak - measured.true_peak;
+    loudness_gain.min(peak_headroom)
+}
+
+pub fn audio_file_extension(audio_entry: &Audio) -> String {
+    Path::new(&audio_entry.path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_string())
+        .unwrap_or(audio_entry.audio_type.clone())
+}
+
+async fn normalization_filter(source: &Audio, options: &NormalizeOptions) -> Result<String, String> {
+    let target: LoudnessTarget = options.resolved_target();
+
+    match options.mode.unwrap_or(NormalizeMode::TwoPass) {
+        NormalizeMode::TwoPass => {
+            let args: Vec<String> = vec![
+                "-hide_banner".to_string(),
+                "-i".to_string(), source.path.clone(),
+                "-af".to_string(), loudnorm_filter(&target, None),
+                "-f".to_string(), "null".to_string(), "-".to_string(),
+            ];
+            let output = run_ffmpeg(&args).await?;
+            let stats: LoudnormStats = parse_loudnorm_stats(&output.stderr)?;
+            Ok(loudnorm_filter(&target, Some(&stats)))
+        }
+        NormalizeMode::Gain => {
+            let measured: LoudnessMeasurement = match (source.integrated_lufs, source.true_peak, source.loudness_range) {
+                (Some(integrated_lufs), Some(true_peak), Some(loudness_range)) => LoudnessMeasurement { integrated_lufs, true_peak, loudness_range },
+                _ => measure_file_loudness(&source.path).await?,
+            };
+            Ok(format!("volume={:.2}dB", normalization_gain_db(&measured, &target)))
+        }
+    }
+}
+
+async fn render_normalized(source: &Audio, filter: &str, destination: &Path) -> Result<(), String> {
+    let mut args: Vec<String> = vec![
+        "-hide_banner".to_string(),
+        "-i".to_string(), source.path.clone(),
+        "-af".to_string(), filter.to_string(),
+        "-c:v".to_string(), "copy".to_string(),
+        "-map_metadata".to_string(), "0".to_string(),
+    ];
+    // loudnorm resamples to 192 kHz internally, keep the rate of the source
+    if let Ok(info) = probe_audio(&source.path) {
+        args.push("-ar".to_string());
+        args.push(info.sample_rate.to_string());
+    }
+    args.push("-y".to_string());
+    args.push(destination.to_str().unwrap().to_string());
+
+    run_ffmpeg(&args).await?;
+    Ok(())
+}
+
+async fn normalize_single(source: &Audio, options: &NormalizeOptions) -> Result<NormalizeResult, String> {
+    let target: LoudnessTarget = options.resolved_target();
+    let extension: String = audio_file_extension(source);
+    let filter: String = normalization_filter(source, options).await?;
+
+    if options.in_place.unwrap_or(false) {
+        let source_path: PathBuf = PathBuf::from(&source.path);
+        let source_dir: &Path = source_path.parent().ok_or("Invalid audio path")?;
+        let stem: &str = source_path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid audio path")?;
+
+        let rendered_path: PathBuf = next_available_path(source_dir, &format!("{}-normalizing", stem), &extension)?;
+        render_normalized(source, &filter, &rendered_path).await
+            .map_err(|e| discard_output_path(&rendered_path, e))?;
+
+        // until the original is replaced, a failure leaves it as it was and removes what was written
+        let backup_dir: PathBuf = audio_store_path().join("backups");
+        std::fs::create_dir_all(&backup_dir)
+            .map_err(|e| discard_output_path(&rendered_path, format!("Unable to create backup directory: {}", e)))?;
+        let backup_path: PathBuf = next_available_path(&backup_dir, stem, &extension)
+            .map_err(|e| discard_output_path(&rendered_path, e))?;
+        std::fs::copy(&source_path, &backup_path).map_err(|e| {
+            let err: String = discard_output_path(&backup_path, format!("Unable to back up original: {}", e));
+            discard_output_path(&rendered_path, err)
+        })?;
+        std::fs::rename(&rendered_path, &source_path).map_err(|e| {
+            let err: String = discard_output_path(&backup_path, format!("Unable to replace original: {}", e));
+            discard_output_path(&rendered_path, err)
+        })?;
+
+        // the original is replaced at this point, a failed measurement does not undo that
+        let error: Option<String> = analyze_and_store_loudness(source.id, &source.path).await
+            .err()
+            .map(|err| format!("Normalized, but measuring the result failed: {}", err));
+
+        return Ok(NormalizeResult {
+            audio_id: source.id,
+            output_audio_id: Some(source.id),
+            output_path: Some(source.path.clone()),
+            backup_path: Some(backup_path.to_str().unwrap().to_string()),
+            error,
+        });
+    }
+
+    create_audio_store_directory()?;
+    let base_file_name: String = format!("{}-normalized-to-{}-LUFS", trim_invalid_file_characters(&source.title), target.integrated_lufs);
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), &base_file_name, &extension)?;
+    render_normalized(source, &filter, &destination_path).await
+        .map_err(|e| discard_output_path(&destination_path, e))?;
+
+    let created: Audio = insert_audio_entry(&NewAudio {
+        title: &base_file_name,
+        author: &source.author,
+        path: destination_path.to_str().unwrap(),
+        duration: &source.duration,
+        audio_type: &source.audio_type,
+        album: source.album.as_deref(),
+        genre: source.genre.as_deref(),
+        comment: source.comment.as_deref(),
+        artwork_hash: source.artwork_hash.as_deref(),
+        source_audio_id: Some(source.id),
+        ..Default::default()
+    })?;
+    let error: Option<String> = analyze_and_store_loudness(created.id, &created.path).await
+        .err()
+        .map(|err| format!("Normalized, but measuring the result failed: {}", err));
+
+    Ok(NormalizeResult {
+        audio_id: source.id,
+        output_audio_id: Some(created.id),
+        output_path: Some(created.path),
+        backup_path: None,
+        error,
+    })
+}
+
+#[tauri::command]
+pub async fn normalize_audio(audio_ids: Vec<i32>, options: NormalizeOptions) -> Result<Vec<NormalizeResult>, String> {
+    let mut results: Vec<NormalizeResult> = Vec::with_capacity(audio_ids.len());
+
+    for audio_id in audio_ids {
+        let result: Result<NormalizeResult, String> = match find_audio(audio_id) {
+            Ok(source) => normalize_single(&source, &options).await,
+            Err(err) => Err(err),
+        };
+        results.push(result.unwrap_or_else(|err| NormalizeResult {
+            audio_id,
+            output_audio_id: None,
+            output_path: None,
+            backup_path: None,
+            error: Some(err),
+        }));
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOUDNORM_STDERR: &str = r#"Input #0, mp3, from 'input.mp3':
+  Duration: 00:03:12.45, start: 0.025057, bitrate: 320 kb/s
+[Parsed_loudnorm_0 @ 0x55d4c1b3a840]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}
+size=N/A time=00:03:12.45 bitrate=N/A speed= 412x"#;
+
+    #[test]
+    fn test_parse_loudnorm_stats() {
+        let stats = parse_loudnorm_stats(LOUDNORM_STDERR).unwrap();
+        assert_eq!(stats, LoudnormStats {
+            input_i: -27.61,
+            input_tp: -4.47,
+            input_lra: 18.06,
+            input_thresh: -39.20,
+            target_offset: 0.58,
+        });
+    }
+
+    #[test]
+    fn test_parse_loudnorm_stats_silence() {
+        let stderr = LOUDNORM_STDERR.replace("\"-27.61\"", "\"-inf\"");
+        assert!(parse_loudnorm_stats(&stderr).is_err());
+    }
+
+    #[test]
+    fn test_parse_loudnorm_stats_missing() {
+        assert!(parse_loudnorm_stats("ffmpeg version 6.0").is_err());
+    }
+
+    #[test]
+    fn test_loudnorm_filter() {
+        let target = NormalizationPreset::Broadcast.target();
+        assert_eq!(loudnorm_filter(&target, None), "loudnorm=I=-23:TP=-1:LRA=20:print_format=json");
+
+        let stats = parse_loudnorm_stats(LOUDNORM_STDERR).unwrap();
+        assert_eq!(
+            loudnorm_filter(&target, Some(&stats)),
+            "loudnorm=I=-23:TP=-1:LRA=20:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.2:offset=0.58:linear=true"
+        );
+    }
+
+    #[test]
+    fn test_normalization_gain_db() {
+        let target = NormalizationPreset::Streaming.target();
+        let quiet = LoudnessMeasurement { integrated_lufs: -20.0, loudness_range: 5.0, true_peak: -10.0 };
+        assert_eq!(normalization_gain_db(&quiet, &target), 6.0);
+
+        // limited by the true peak target
+        let peaky = LoudnessMeasurement { integrated_lufs: -20.0, loudness_range: 5.0, true_peak: -3.0 };
+        assert_eq!(normalization_gain_db(&peaky, &target), 2.0);
+
+        // too loud material is attenuated
+        let loud = LoudnessMeasurement { integrated_lufs: -8.0, loudness_range: 5.0, true_peak: 0.0 };
+        assert_eq!(normalization_gain_db(&loud, &target), -6.0);
+    }
+
+    #[test]
+    fn test_resolved_target_prefers_explicit_target() {
+        let custom = LoudnessTarget { integrated_lufs: -18.0, true_peak: -2.0, loudness_range: 7.0 };
+        let options = NormalizeOptions { preset: Some(NormalizationPreset::Podcast), target: Some(custom), mode: None, in_place: None };
+        assert_eq!(options.resolved_target(), custom);
+
+        let options = NormalizeOptions { preset: None, target: None, mode: None, in_place: None };
+        assert_eq!(options.resolved_target(), NormalizationPreset::Streaming.target());
+    }
+}
@@ -0,0 +1,345 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::commands::audio_commands::{find_audio, insert_audio_entry, spawn_import_analysis};
+use crate::commands::playlist_commands::get_all_audio_from_playlist;
+use crate::commands::processing::effects::AudioEffect;
+use crate::commands::processing::normalize::{
+    audio_file_extension, loudnorm_filter, parse_loudnorm_stats, LoudnessTarget, LoudnormStats, NormalizationPreset,
+};
+use crate::commands::recipe_commands::find_recipe;
+use crate::helper::constants::audio_store_path;
+use crate::helper::decoder::{probe_audio, probe_duration, AudioInfo};
+use crate::helper::files::{create_audio_store_directory, discard_output_path, next_available_path, trim_invalid_file_characters};
+use crate::helper::sidecar::run_ffmpeg;
+use crate::helper::tools::seconds_to_minutes;
+use crate::models::audio_model::{Audio, NewAudio};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RecipeStep {
+    // cut points in seconds on the timeline produced by the previous steps
+    Trim { start: f64, end: f64 },
+    Normalize { preset: Option<NormalizationPreset>, target: Option<LoudnessTarget> },
+    Effect { effect: AudioEffect },
+    // output container and stream parameters, only allowed as the last step
+    Transcode { format: String, sample_rate: Option<u32>, channels: Option<u16> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecipeDefinition {
+    pub id: i32,
+    pub name: String,
+    pub steps: Vec<RecipeStep>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecipeResult {
+    pub audio_id: i32,
+    pub output_audio_id: Option<i32>,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipeOutput {
+    pub extension: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+// the whole recipe as a single ffmpeg filter graph
+#[derive(Debug, PartialEq)]
+pub struct RecipeGraph {
+    pub filters: Vec<String>,
+    // filters up to a measuring loudnorm, set while the loudness of the intermediate result is unknown
+    pub measure_filters: Option<Vec<String>>,
+    pub output: RecipeOutput,
+    // length of the result in seconds
+    pub duration: f64,
+}
+
+pub fn validate_steps(steps: &[RecipeStep]) -> Result<(), String> {
+    if steps.is_empty() {
+        return Err("A recipe needs at least one step".to_string());
+    }
+    let normalize_steps: usize = steps.iter().filter(|step| matches!(step, RecipeStep::Normalize { .. })).count();
+    if normalize_steps > 1 {
+        return Err("A recipe can only normalize once".to_string());
+    }
+    for (index, step) in steps.iter().enumerate() {
+        match step {
+            RecipeStep::Trim { start, end } => {
+                if !start.is_finite() || !end.is_finite() || *start < 0.0 || end <= start {
+                    return Err(format!("Invalid trim range {}-{}", start, end));
+                }
+            }
+            RecipeStep::Effect { effect } => effect.validate()?,
+            RecipeStep::Transcode { format, .. } => {
+                if index != steps.len() - 1 {
+                    return Err("Transcode must be the last step of a recipe".to_string());
+                }
+                if format.is_empty() || !format.chars().all(|c| c.is_ascii_alphanumeric()) {
+                    return Err(format!("Invalid output format {}", format));
+                }
+            }
+            RecipeStep::Normalize { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+// builds the filter graph for a source with the given stream parameters. Normalization needs the loudness
+// of the audio it receives, so without `measured` the graph also carries the prefix to measure it with
+pub fn recipe_graph(steps: &[RecipeStep], info: &AudioInfo, measured: Option<&LoudnormStats>) -> Result<RecipeGraph, String> {
+    validate_steps(steps)?;
+
+    let mut duration: f64 = info.duration_secs().ok_or("Unable to determine duration")?;
+    let mut filters: Vec<String> = Vec::new();
+    let mut measure_filters: Option<Vec<String>> = None;
+    let mut output = RecipeOutput { extension: None, sample_rate: None, channels: None };
+
+    for step in steps {
+        match step {
+            RecipeStep::Trim { start, end } => {
+                if *start >= duration {
+                    return Err(format!("Trim starts after the end of the clip ({:.3} s)", duration));
+                }
+                filters.push(format!("atrim=start={:.3}:end={:.3},asetpts=PTS-STARTPTS", start, end));
+                duration = end.min(duration) - start;
+            }
+            RecipeStep::Normalize { preset, target } => {
+                let target: LoudnessTarget = target.unwrap_or_else(|| preset.unwrap_or(NormalizationPreset::Streaming).target());
+                // loudnorm resamples to 192 kHz internally, keep the rate of the source
+                let resample: String = format!("aresample={}", info.sample_rate);
+                if measured.is_none() {
+                    let mut prefix: Vec<String> = filters.clone();
+                    prefix.push(loudnorm_filter(&target, None));
+                    measure_filters = Some(prefix);
+                }
+                filters.push(format!("{},{}", loudnorm_filter(&target, measured), resample));
+            }
+            RecipeStep::Effect { effect } => {
+                let current = AudioInfo {
+                    sample_rate: info.sample_rate,
+                    channels: info.channels,
+                    total_frames: Some((duration * info.sample_rate as f64).round() as u64),
+                };
+                filters.push(effect.filter(&current)?);
+                if let AudioEffect::Speed { factor } = effect {
+                    duration /= factor;
+                }
+            }
+            RecipeStep::Transcode { format, sample_rate, channels } => {
+                output = RecipeOutput { extension: Some(format.to_lowercase()), sample_rate: *sample_rate, channels: *channels };
+            }
+        }
+    }
+
+    Ok(RecipeGraph { filters, measure_filters, output, duration })
+}
+
+async fn measure_intermediate(source_path: &str, measure_filters: &[String]) -> Result<LoudnormStats, String> {
+    let args: Vec<String> = vec![
+        "-hide_banner".to_string(),
+        "-i".to_string(), source_path.to_string(),
+        "-af".to_string(), measure_filters.join(","),
+        "-f".to_string(), "null".to_string(), "-".to_string(),
+    ];
+    let output = run_ffmpeg(&args).await?;
+    parse_loudnorm_stats(&output.stderr)
+}
+
+async fn render_graph(source_path: &str, graph: &RecipeGraph, keep_artwork: bool, destination: &Path) -> Result<(), String> {
+    let mut args: Vec<String> = vec!["-hide_banner".to_string(), "-i".to_string(), source_path.to_string()];
+    if !graph.filters.is_empty() {
+        args.push("-af".to_string());
+        args.push(graph.filters.join(","));
+    }
+    if let Some(sample_rate) = graph.output.sample_rate {
+        args.push("-ar".to_string());
+        args.push(sample_rate.to_string());
+    }
+    if let Some(channels) = graph.output.channels {
+        args.push("-ac".to_string());
+        args.push(channels.to_string());
+    }
+    // cover art can only be copied into a container of the same kind
+    if keep_artwork {
+        args.extend(["-c:v".to_string(), "copy".to_string()]);
+    } else {
+        args.push("-vn".to_string());
+    }
+    args.extend(["-map_metadata".to_string(), "0".to_string(), "-y".to_string()]);
+    args.push(destination.to_str().unwrap().to_string());
+
+    run_ffmpeg(&args).await?;
+    Ok(())
+}
+
+// runs the recipe on one entry and adds only the final result to the library
+pub async fn apply_recipe_to_audio(source: &Audio, recipe_name: &str, steps: &[RecipeStep]) -> Result<Audio, String> {
+    let info: AudioInfo = probe_audio(&source.path)?;
+    let mut graph: RecipeGraph = recipe_graph(steps, &info, None)?;
+    if let Some(measure_filters) = &graph.measure_filters {
+        let stats: LoudnormStats = measure_intermediate(&source.path, measure_filters).await?;
+        graph = recipe_graph(steps, &info, Some(&stats))?;
+    }
+
+    let source_extension: String = audio_file_extension(source);
+    let extension: String = graph.output.extension.clone().unwrap_or(source_extension.clone());
+
+    create_audio_store_directory()?;
+    let base_file_name: String = format!("{}-{}", trim_invalid_file_characters(&source.title), trim_invalid_file_characters(recipe_name));
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), &base_file_name, &extension)?;
+    render_graph(&source.path, &graph, extension == source_extension, &destination_path).await
+        .map_err(|e| discard_output_path(&destination_path, e))?;
+
+    let output_duration: f64 = probe_duration(destination_path.to_str().unwrap()).unwrap_or(graph.duration);
+    let created: Audio = insert_audio_entry(&NewAudio {
+        title: &base_file_name,
+        author: &source.author,
+        path: destination_path.to_str().unwrap(),
+        duration: &seconds_to_minutes(output_duration.round() as u64),
+        audio_type: graph.output.extension.as_deref().unwrap_or(&source.audio_type),
+        album: source.album.as_deref(),
+        genre: source.genre.as_deref(),
+        comment: source.comment.as_deref(),
+        artwork_hash: source.artwork_hash.as_deref(),
+        source_audio_id: Some(source.id),
+        ..Default::default()
+    })?;
+    spawn_import_analysis(created.id, created.path.clone());
+
+    Ok(created)
+}
+
+pub async fn apply_recipe_to_selection(recipe: &RecipeDefinition, audio_ids: Vec<i32>) -> Vec<RecipeResult> {
+    let mut results: Vec<RecipeResult> = Vec::with_capacity(audio_ids.len());
+
+    for audio_id in audio_ids {
+        let result: Result<Audio, String> = match find_audio(audio_id) {
+            Ok(source) => apply_recipe_to_audio(&source, &recipe.name, &recipe.steps).await,
+            Err(err) => Err(err),
+        };
+        results.push(match result {
+            Ok(created) => RecipeResult { audio_id, output_audio_id: Some(created.id), output_path: Some(created.path), error: None },
+            Err(err) => RecipeResult { audio_id, output_audio_id: None, output_path: None, error: Some(err) },
+        });
+    }
+
+    results
+}
+
+// applies a stored recipe to the given entries and to every entry of the playlist
+#[tauri::command(async)]
+pub async fn apply_recipe(recipe_id: i32, audio_ids: Option<Vec<i32>>, playlist_id: Option<i32>) -> Result<Vec<RecipeResult>, String> {
+    let recipe: RecipeDefinition = find_recipe(recipe_id)?;
+
+    let mut targets: Vec<i32> = audio_ids.unwrap_or_default();
+    if let Some(playlist_id) = playlist_id {
+        for playlist_entry in get_all_audio_from_playlist(playlist_id)? {
+            if !targets.contains(&playlist_entry.id) {
+                targets.push(playlist_entry.id);
+            }
+        }
+    }
+    if targets.is_empty() {
+        return Err("No audio selected".to_string());
+    }
+
+    Ok(apply_recipe_to_selection(&recipe, targets).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(seconds: f64) -> AudioInfo {
+        AudioInfo { sample_rate: 44100, channels: 2, total_frames: Some((44100.0 * seconds) as u64) }
+    }
+
+    fn stats() -> LoudnormStats {
+        LoudnormStats { input_i: -20.0, input_tp: -3.0, input_lra: 5.0, input_thresh: -30.0, target_offset: 0.1 }
+    }
+
+    fn daily_recipe() -> Vec<RecipeStep> {
+        vec![
+            RecipeStep::Trim { start: 5.0, end: 35.0 },
+            RecipeStep::Normalize { preset: Some(NormalizationPreset::Broadcast), target: None },
+            RecipeStep::Effect { effect: AudioEffect::Fade { fade_in: Some(1.0), fade_out: Some(2.0) } },
+            RecipeStep::Transcode { format: "wav".to_string(), sample_rate: Some(48000), channels: None },
+        ]
+    }
+
+    #[test]
+    fn test_recipe_graph_measures_before_normalizing() {
+        let graph = recipe_graph(&daily_recipe(), &info(60.0), None).unwrap();
+        assert_eq!(graph.measure_filters, Some(vec![
+            "atrim=start=5.000:end=35.000,asetpts=PTS-STARTPTS".to_string(),
+            "loudnorm=I=-23:TP=-1:LRA=20:print_format=json".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn test_recipe_graph() {
+        let graph = recipe_graph(&daily_recipe(), &info(60.0), Some(&stats())).unwrap();
+        assert_eq!(graph.measure_filters, None);
+        assert_eq!(graph.filters, vec![
+            "atrim=start=5.000:end=35.000,asetpts=PTS-STARTPTS".to_string(),
+            "loudnorm=I=-23:TP=-1:LRA=20:measured_I=-20:measured_TP=-3:measured_LRA=5:measured_thresh=-30:offset=0.1:linear=true,aresample=44100".to_string(),
+            // the fade out is placed at the end of the trimmed clip
+            "afade=t=in:st=0:d=1.000,afade=t=out:st=28.000:d=2.000".to_string(),
+        ]);
+        assert_eq!(graph.output, RecipeOutput { extension: Some("wav".to_string()), sample_rate: Some(48000), channels: None });
+        assert!((graph.duration - 30.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_recipe_graph_tracks_speed_changes() {
+        let steps = vec![
+            RecipeStep::Effect { effect: AudioEffect::Speed { factor: 2.0 } },
+            RecipeStep::Effect { effect: AudioEffect::Fade { fade_in: None, fade_out: Some(1.0) } },
+        ];
+        let graph = recipe_graph(&steps, &info(10.0), None).unwrap();
+        assert_eq!(graph.filters[1], "afade=t=out:st=4.000:d=1.000");
+        assert!((graph.duration - 5.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_recipe_graph_trim_past_end() {
+        let steps = vec![RecipeStep::Trim { start: 20.0, end: 30.0 }];
+        assert!(recipe_graph(&steps, &info(10.0), None).is_err());
+    }
+
+    #[test]
+    fn test_validate_steps() {
+        assert!(validate_steps(&daily_recipe()).is_ok());
+        assert!(validate_steps(&[]).is_err());
+
+        let mut transcode_first = daily_recipe();
+        transcode_first.rotate_right(1);
+        assert!(validate_steps(&transcode_first).is_err());
+
+        let twice = vec![
+            RecipeStep::Normalize { preset: None, target: None },
+            RecipeStep::Normalize { preset: None, target: None },
+        ];
+        assert!(validate_steps(&twice).is_err());
+
+        let bad_format = vec![RecipeStep::Transcode { format: "../wav".to_string(), sample_rate: None, channels: None }];
+        assert!(validate_steps(&bad_format).is_err());
+    }
+
+    #[test]
+    fn test_recipe_steps_json() {
+        let json = r#"[
+            {"type":"trim","start":1.5,"end":10},
+            {"type":"normalize","preset":"podcast","target":null},
+            {"type":"effect","effect":{"type":"gain","db":-2}},
+            {"type":"transcode","format":"wav","sample_rate":48000,"channels":2}
+        ]"#;
+        let steps: Vec<RecipeStep> = serde_json::from_str(json).unwrap();
+        assert_eq!(steps[2], RecipeStep::Effect { effect: AudioEffect::Gain { db: -2.0 } });
+        assert_eq!(serde_json::from_str::<Vec<RecipeStep>>(&serde_json::to_string(&steps).unwrap()).unwrap(), steps);
+    }
+}
@@ -0,0 +1,243 @@
+use serde::{Serialize, Deserialize};
+use crate::commands::analysis::silence::{detect_file_silence, non_silent_segments, TimeRange};
+use crate::commands::audio_commands::{delete_audio, find_audio, spawn_import_analysis};
+use crate::commands::playlist_audio_commands::insert_audio_into_playlist;
+use crate::commands::playlist_commands::{delete_playlist, insert_playlist_entry};
+use crate::commands::processing::trimming::{trim_to_new_entry, trim_to_titled_entry};
+use crate::commands::youtube::youtube_commands::fetch_metadata;
+use crate::helper::files::trim_invalid_file_characters;
+use crate::models::audio_model::Audio;
+use crate::models::playlist_model::{NewPlaylist, Playlist, PlaylistArg};
+use crate::models::remote_audio_model::RemoteChapter;
+
+// chapters shorter than this after fitting them to the entry are not worth a clip
+const MIN_CHAPTER_DURATION: f64 = 0.5;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitResult {
+    pub segments: Vec<Audio>,
+    // set when the segments were collected into a new playlist
+    pub playlist_id: Option<i32>,
+}
+
+// creates one entry per non-silent segment of the clip. Segments shorter than `min_segment_duration`
+// (default 0.5 s) are dropped, and when `playlist` is given the segments are added to a new playlist.
+// When a segment fails, the ones created before it are removed again
+#[tauri::command(async)]
+pub async fn split_on_silence(
+    audio_id: i32,
+    threshold_db: f64,
+    min_duration: f64,
+    min_segment_duration: Option<f64>,
+    playlist: Option<PlaylistArg>,
+) -> Result<SplitResult, String> {
+    let source: Audio = find_audio(audio_id)?;
+    let (silences, total) = detect_file_silence(&source.path, threshold_db, min_duration).await?;
+    let segments: Vec<TimeRange> = non_silent_segments(&silences, total, min_segment_duration.unwrap_or(0.5));
+    if segments.is_empty() {
+        return Err("No non-silent segments found".to_string());
+    }
+
+    let base_file_name: String = trim_invalid_file_characters(&source.title);
+    let mut created: Vec<Audio> = Vec::with_capacity(segments.len());
+    for (index, segment) in segments.iter().enumerate() {
+        let segment_name: String = format!("{}-part-{}", base_file_name, index + 1);
+        match trim_to_new_entry(&source, segment.start, segment.end, None, &segment_name).await {
+            Ok(segment_audio) => created.push(segment_audio),
+            Err(err) => return Err(discard_segments(&created, err)),
+        }
+    }
+
+    finish_split(created, playlist, &source.title)
+}
+
+// removes the entries and files of a split that failed part way, so it leaves the library as it was
+fn discard_segments(segments: &[Audio], err: String) -> String {
+    for segment_audio in segments {
+        let _ = delete_audio(segment_audio.id);
+        let _ = std::fs::remove_file(&segment_audio.path);
+    }
+    err
+}
+
+// collects the segments of a split into the requested playlist and analyzes them once all of them exist
+fn finish_split(segments: Vec<Audio>, playlist: Option<PlaylistArg>, default_title: &str) -> Result<SplitResult, String> {
+    let playlist_id: Option<i32> = collect_into_playlist(&segments, playlist, default_title)
+        .map_err(|e| discard_segments(&segments, e))?;
+    for segment_audio in &segments {
+        spawn_import_analysis(segment_audio.id, segment_audio.path.clone());
+    }
+    Ok(SplitResult { segments, playlist_id })
+}
+
+// adds the segments to a new playlist, titled `default_title` unless the argument names it.
+// The playlist is deleted again when a segment cannot be added
+fn collect_into_playlist(segments: &[Audio], playlist: Option<PlaylistArg>, default_title: &str) -> Result<Option<i32>, String> {
+    let Some(playlist_arg) = playlist else { return Ok(None) };
+    let new_playlist: Playlist = insert_playlist_entry(&NewPlaylist {
+        title: &playlist_arg.title.unwrap_or(default_title.to_string()),
+        created_on: &playlist_arg.created_on.unwrap_or_default(),
+    })?;
+    let filled: Result<(), String> = segments.iter()
+        .try_for_each(|segment_audio| insert_audio_into_playlist(new_playlist.id, segment_audio.id));
+    if let Err(err) = filled {
+        let _ = delete_playlist(new_playlist.id);
+        return Err(err);
+    }
+    Ok(Some(new_playlist.id))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterSegment {
+    pub title: String,
+    // seconds from the start of the entry's file
+    pub start: f64,
+    pub end: f64,
+}
+
+// places the chapters of a video on an entry holding `source_start` to `source_end` of it.
+// Chapters outside the entry are dropped and those crossing its edges are cut to fit
+pub fn chapter_segments(chapters: &[RemoteChapter], source_start: Option<f64>, source_end: Option<f64>) -> Vec<ChapterSegment> {
+    let offset: f64 = source_start.unwrap_or(0.0);
+    let limit: f64 = source_end.unwrap_or(f64::INFINITY);
+    chapters.iter()
+        .enumerate()
+        .filter_map(|(index, chapter)| {
+            let start: f64 = chapter.start_time.max(offset);
+            let end: f64 = chapter.end_time.min(limit);
+            if !start.is_finite() || !end.is_finite() || end - start < MIN_CHAPTER_DURATION {
+                return None;
+            }
+            let title: String = match chapter.title.trim() {
+                "" => format!("Chapter {}", index + 1),
+                title => title.to_string(),
+            };
+            Some(ChapterSegment { title, start: start - offset, end: end - offset })
+        })
+        .collect()
+}
+
+// the chapters of the video an entry was downloaded from
+pub async fn source_chapters(source: &Audio) -> Result<Vec<RemoteChapter>, String> {
+    let url: String = match (&source.source_url, &source.source_video_id) {
+        (Some(url), _) => url.clone(),
+        (None, Some(video_id)) => format!("https://www.youtube.com/watch?v={}", video_id),
+        (None, None) => return Err(format!("{} was not downloaded from a video", source.title)),
+    };
+    Ok(fetch_metadata(url).await?.chapters)
+}
+
+// creates one entry per chapter, titled by the chapter, and optionally collects them into a new
+// playlist named after the source
+pub async fn split_entry_by_chapters(source: &Audio, chapters: &[RemoteChapter], playlist: Option<PlaylistArg>) -> Result<SplitResult, String> {
+    let segments: Vec<ChapterSegment> = chapter_segments(chapters, source.source_start, source.source_end);
+    if segments.is_empty() {
+        return Err(format!("{} has no chapters", source.title));
+    }
+
+    let base_file_name: String = trim_invalid_file_characters(&source.title);
+    let mut created: Vec<Audio> = Vec::with_capacity(segments.len());
+    for (index, segment) in segments.iter().enumerate() {
+        let segment_name: String = format!("{}-{}-{}", base_file_name, index + 1, trim_invalid_file_characters(&segment.title));
+        match trim_to_titled_entry(source, segment.start, segment.end, None, &segment_name, &segment.title).await {
+            Ok(chapter_audio) => created.push(chapter_audio),
+            Err(err) => return Err(discard_segments(&created, err)),
+        }
+    }
+
+    finish_split(created, playlist, &source.title)
+}
+
+// splits an entry by the chapters of the video it was downloaded from; `chapters` replaces
+// looking them up
+#[tauri::command(async)]
+pub async fn split_by_chapters(audio_id: i32, chapters: Option<Vec<RemoteChapter>>, playlist: Option<PlaylistArg>) -> Result<SplitResult, String> {
+    let source: Audio = find_audio(audio_id)?;
+    let chapters: Vec<RemoteChapter> = match chapters {
+        Some(chapters) => chapters,
+        None => source_chapters(&source).await?,
+    };
+    split_entry_by_chapters(&source, &chapters, playlist).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::audio_commands::insert_audio_entry;
+    use crate::commands::playlist_commands::{get_all_playlists, get_playlist_audio_ids};
+    use crate::db::test_utils::fresh_database;
+    use crate::models::audio_model::NewAudio;
+
+    fn chapter(title: &str, start_time: f64, end_time: f64) -> RemoteChapter {
+        RemoteChapter { title: title.to_string(), start_time, end_time }
+    }
+
+    #[test]
+    fn test_chapter_segments_whole_video() {
+        let chapters = vec![chapter("Intro", 0.0, 30.0), chapter("Verse", 30.0, 95.5), chapter(" ", 95.5, 120.0)];
+        assert_eq!(chapter_segments(&chapters, None, None), vec![
+            ChapterSegment { title: "Intro".to_string(), start: 0.0, end: 30.0 },
+            ChapterSegment { title: "Verse".to_string(), start: 30.0, end: 95.5 },
+            ChapterSegment { title: "Chapter 3".to_string(), start: 95.5, end: 120.0 },
+        ]);
+    }
+
+    #[test]
+    fn test_chapter_segments_of_section() {
+        // the entry holds 20 s to 100 s of the video
+        let chapters = vec![chapter("Intro", 0.0, 20.25), chapter("Verse", 20.25, 60.0), chapter("Chorus", 60.0, 140.0), chapter("Outro", 140.0, 180.0)];
+        assert_eq!(chapter_segments(&chapters, Some(20.0), Some(100.0)), vec![
+            ChapterSegment { title: "Verse".to_string(), start: 0.25, end: 40.0 },
+            ChapterSegment { title: "Chorus".to_string(), start: 40.0, end: 80.0 },
+        ]);
+    }
+
+    fn insert_test_audio(title: &str) -> Audio {
+        insert_audio_entry(&NewAudio {
+            title,
+            author: "",
+            path: &format!("/nonexistent/{}.wav", title),
+            duration: "0:01",
+            audio_type: "wav",
+            ..Default::default()
+        }).unwrap()
+    }
+
+    fn insert_test_playlist(title: &str) -> Playlist {
+        insert_playlist_entry(&NewPlaylist { title, created_on: "" }).unwrap()
+    }
+
+    #[test]
+    fn test_discard_segments_keeps_other_playlists() {
+        let _database = fresh_database();
+        let segment_audio = insert_test_audio("segment");
+        let other_audio = insert_test_audio("other");
+        // the first playlist shares its id with the segment
+        let colliding = insert_test_playlist("colliding");
+        let holding_segment = insert_test_playlist("holding segment");
+        assert_eq!(colliding.id, segment_audio.id);
+        insert_audio_into_playlist(colliding.id, other_audio.id).unwrap();
+        insert_audio_into_playlist(holding_segment.id, segment_audio.id).unwrap();
+
+        let err = discard_segments(std::slice::from_ref(&segment_audio), "failed".to_string());
+        assert_eq!(err, "failed");
+        assert!(find_audio(segment_audio.id).is_err());
+        assert_eq!(get_playlist_audio_ids(colliding.id).unwrap(), vec![other_audio.id]);
+        assert!(get_playlist_audio_ids(holding_segment.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_collect_removes_the_playlist() {
+        let _database = fresh_database();
+        let segment_audio = insert_test_audio("segment");
+        let existing = insert_test_playlist("existing");
+
+        // adding the same entry twice fails on the second insert
+        let segments = vec![find_audio(segment_audio.id).unwrap(), segment_audio];
+        let playlist = Some(PlaylistArg { title: Some("split".to_string()), created_on: None });
+        assert!(finish_split(segments, playlist, "source").is_err());
+
+        let playlist_ids: Vec<i32> = get_all_playlists().unwrap().iter().map(|playlist| playlist.id).collect();
+        assert_eq!(playlist_ids, vec![existing.id]);
+    }
+}
@@ -1,16 +1,14 @@
-
-use diesel::prelude::*;
-use crate::db::establish_connection;
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+use tokio::task;
+use crate::commands::audio_commands::{insert_audio_entry, spawn_import_analysis};
+use crate::commands::transcode_preset_commands::find_transcode_preset;
 use crate::helper::constants::audio_store_path;
-use crate::helper::files::trim_invalid_file_characters;
+use crate::helper::files::{discard_output_path, next_available_path, trim_invalid_file_characters};
 pub use crate::helper::files::{create_audio_store_directory, construct_output_path};
-use crate::models::audio_model::NewAudio;
-use crate::schema::audio::dsl::*;
-use tokio::sync::mpsc;
-use tokio::task;
-use serde::{Serialize, Deserialize};   
-use std::sync::{Arc, Mutex};     
-use std::collections::HashSet;
+use crate::helper::sidecar::run_ffmpeg;
+use crate::helper::tags::read_front_cover;
+use crate::models::audio_model::{Audio, NewAudio};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct QueueItem {
@@ -20,101 +18,421 @@ pub struct QueueItem {
     pub duration: String,
     pub converted_type: String,
     pub is_added_to_list: bool,
+    // encoder settings, taken from `preset_id` or ffmpeg defaults for `converted_type` when unset
+    pub options: Option<TranscodeOptions>,
+    pub preset_id: Option<i32>,
 }
 
-#[tauri::command]
-pub async fn transcode_audio(queue_items: Vec<QueueItem>) -> Result<(), String> {
-    use tauri::api::process::Command;
-    create_audio_store_directory()?;
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioCodec {
+    Mp3,
+    Aac,
+    Opus,
+    Vorbis,
+    Flac,
+    Alac,
+    // uncompressed, the bit depth picks the sample format
+    Pcm,
+}
+
+impl AudioCodec {
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, AudioCodec::Flac | AudioCodec::Alac | AudioCodec::Pcm)
+    }
+
+    // codec used when only the container is given
+    pub fn default_for_format(format: &str) -> Option<AudioCodec> {
+        match format {
+            "mp3" => Some(AudioCodec::Mp3),
+            "m4a" | "aac" => Some(AudioCodec::Aac),
+            "opus" => Some(AudioCodec::Opus),
+            "ogg" => Some(AudioCodec::Vorbis),
+            "flac" => Some(AudioCodec::Flac),
+            "wav" => Some(AudioCodec::Pcm),
+            _ => None,
+        }
+    }
+
+    fn fits_format(&self, format: &str) -> bool {
+        match format {
+            "mp3" => *self == AudioCodec::Mp3,
+            "aac" => *self == AudioCodec::Aac,
+            "m4a" => matches!(self, AudioCodec::Aac | AudioCodec::Alac),
+            "opus" => *self == AudioCodec::Opus,
+            "ogg" => matches!(self, AudioCodec::Vorbis | AudioCodec::Opus | AudioCodec::Flac),
+            "flac" => *self == AudioCodec::Flac,
+            "wav" => *self == AudioCodec::Pcm,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscodeOptions {
+    // output container, also the file extension
+    pub format: String,
+    pub codec: Option<AudioCodec>,
+    // constant bitrate in kbps, for lossy codecs
+    pub bitrate_kbps: Option<u32>,
+    // variable bitrate quality on the scale of the encoder: 0 (best) to 9 for MP3, -1 to 10 (best) for Vorbis
+    pub vbr_quality: Option<f32>,
+    pub sample_rate: Option<u32>,
+    // 16, 24 or 32 for lossless codecs
+    pub bit_depth: Option<u16>,
+    pub channels: Option<u16>,
+    // copy tags and cover art from the source, defaults to true
+    pub keep_metadata: Option<bool>,
+}
+
+impl TranscodeOptions {
+    pub fn for_format(format: &str) -> Self {
+        TranscodeOptions {
+            format: format.to_string(),
+            codec: None,
+            bitrate_kbps: None,
+            vbr_quality: None,
+            sample_rate: None,
+            bit_depth: None,
+            channels: None,
+            keep_metadata: None,
+        }
+    }
+
+    pub fn resolved_codec(&self) -> Result<AudioCodec, String> {
+        match self.codec {
+            Some(codec) => Ok(codec),
+            None => AudioCodec::default_for_format(&self.format).ok_or(format!("Unsupported output format {}", self.format)),
+        }
+    }
 
-    let (tx, mut rx) = mpsc::channel(32);
-    let handles = Arc::new(Mutex::new(Vec::new()));
-    let file_paths = Arc::new(Mutex::new(HashSet::new())); // HashSet to track file paths
-
-    for queue_item in queue_items {
-        let tx = tx.clone();
-        let handles = Arc::clone(&handles);
-        let file_paths = Arc::clone(&file_paths);
-
-        let handle = task::spawn(async move {
-            let base_file_name = format!("{}-converted_to-{}.{}", trim_invalid_file_characters(&queue_item.title), queue_item.converted_type, queue_item.converted_type);
-            let audio_store_path = audio_store_path();
-            let mut destination_path = audio_store_path.join(format!("{}.{}",base_file_name,queue_item.converted_type)); 
-            let mut counter = 1;
-
-            // Lock the HashSet to check for existing paths
-            loop {
-                let mut paths_guard = file_paths.lock().unwrap();
-                if !paths_guard.contains(&destination_path) {
-                    paths_guard.insert(destination_path.clone());
-                    break; // Found a unique path
-                } else {
-                    // Generate a new file name
-                    counter += 1;
-                    let new_file_name = format!("{}-{}", base_file_name, counter);
-                    destination_path = audio_store_path.join(format!("{}.{}",new_file_name,queue_item.converted_type)); 
-                }
+    pub fn validate(&self) -> Result<(), String> {
+        let codec: AudioCodec = self.resolved_codec()?;
+        if !codec.fits_format(&self.format) {
+            return Err(format!("{:?} audio cannot be stored in a {} file", codec, self.format));
+        }
+
+        if self.bitrate_kbps.is_some() && self.vbr_quality.is_some() {
+            return Err("Choose either a bitrate or a VBR quality".to_string());
+        }
+        if let Some(bitrate) = self.bitrate_kbps {
+            let range = match codec {
+                AudioCodec::Mp3 => 8..=320,
+                AudioCodec::Opus => 6..=510,
+                AudioCodec::Aac | AudioCodec::Vorbis => 32..=512,
+                _ => return Err(format!("{:?} is lossless and has no bitrate setting", codec)),
+            };
+            if !range.contains(&bitrate) {
+                return Err(format!("{:?} bitrate must be between {} and {} kbps", codec, range.start(), range.end()));
             }
+        }
+        if let Some(quality) = self.vbr_quality {
+            let valid: bool = match codec {
+                AudioCodec::Mp3 => (0.0..=9.0).contains(&quality),
+                AudioCodec::Vorbis => (-1.0..=10.0).contains(&quality),
+                _ => return Err(format!("{:?} has no VBR quality setting", codec)),
+            };
+            if !valid {
+                return Err(format!("Invalid {:?} VBR quality {}", codec, quality));
+            }
+        }
 
-            let args = vec![
-                "-i", &queue_item.path,
-                &destination_path.to_str().unwrap(),
-            ];
-
-            let command = if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" };
-            Command::new_sidecar(command)
-                .expect("failed to create `my-sidecar` binary command")
-                .args(&args)
-                .spawn()
-                .expect("Failed to spawn sidecar");
-
-            let timeout_duration = std::time::Duration::from_secs(120);
-            let start_time = std::time::Instant::now();
-            // Wait for the output path to exist
-            while !std::path::Path::new(destination_path.to_str().unwrap()).exists() {
-                if start_time.elapsed() > timeout_duration {
-                    panic!("Timeout waiting for output file to exist");
-                }
-                std::thread::sleep(std::time::Duration::from_secs(1));
+        if let Some(bit_depth) = self.bit_depth {
+            let valid: bool = match codec {
+                AudioCodec::Pcm => matches!(bit_depth, 16 | 24 | 32),
+                AudioCodec::Flac | AudioCodec::Alac => matches!(bit_depth, 16 | 24),
+                _ => return Err(format!("{:?} has no bit depth setting", codec)),
+            };
+            if !valid {
+                return Err(format!("{:?} does not support {} bit audio", codec, bit_depth));
             }
-            
-            if queue_item.is_added_to_list {
-                // Fetch metadata and insert into the database
-                let mut connection: SqliteConnection = establish_connection();
-                let new_audio: NewAudio<'_> = NewAudio {
-                    title: &queue_item.title,
-                    author: &queue_item.author,
-                    path: &destination_path.to_str().unwrap(),
-                    duration: &queue_item.duration,
-                    audio_type: &queue_item.converted_type,
-                };
-
-                let result: Result<usize, diesel::result::Error> = diesel::insert_into(audio)
-                    .values(&new_audio)
-                    .execute(&mut connection);
-
-                if result.is_err() {
-                    let _ = tx.send(Err("Error: Could not add audio entry to database".to_string())).await;
-                } else {
-                    let _ = tx.send(Ok(())).await;
-                }
+        }
+
+        if let Some(sample_rate) = self.sample_rate {
+            let valid: bool = match codec {
+                AudioCodec::Opus => matches!(sample_rate, 8000 | 12000 | 16000 | 24000 | 48000),
+                AudioCodec::Mp3 => matches!(sample_rate, 8000 | 11025 | 12000 | 16000 | 22050 | 24000 | 32000 | 44100 | 48000),
+                _ => (8000..=192000).contains(&sample_rate),
+            };
+            if !valid {
+                return Err(format!("{:?} does not support a sample rate of {} Hz", codec, sample_rate));
+            }
+        }
+
+        if let Some(channels) = self.channels {
+            let max_channels: u16 = if codec == AudioCodec::Mp3 { 2 } else { 8 };
+            if channels == 0 || channels > max_channels {
+                return Err(format!("{:?} supports 1 to {} channels", codec, max_channels));
             }
+        }
+        Ok(())
+    }
+
+    // encoder arguments placed between the input and the output path. `has_cover_art` tells whether
+    // the source embeds a picture, any other video stream is dropped
+    pub fn ffmpeg_args(&self, has_cover_art: bool) -> Result<Vec<String>, String> {
+        self.validate()?;
+        let codec: AudioCodec = self.resolved_codec()?;
+        let bit_depth: u16 = self.bit_depth.unwrap_or(16);
+
+        let mut args: Vec<String> = vec!["-c:a".to_string()];
+        args.push(match codec {
+            AudioCodec::Mp3 => "libmp3lame".to_string(),
+            AudioCodec::Aac => "aac".to_string(),
+            AudioCodec::Opus => "libopus".to_string(),
+            AudioCodec::Vorbis => "libvorbis".to_string(),
+            AudioCodec::Flac => "flac".to_string(),
+            AudioCodec::Alac => "alac".to_string(),
+            AudioCodec::Pcm => format!("pcm_s{}le", bit_depth),
         });
 
-        handles.lock().unwrap().push(handle);
+        if let Some(bitrate) = self.bitrate_kbps {
+            args.extend(["-b:a".to_string(), format!("{}k", bitrate)]);
+        }
+        if let Some(quality) = self.vbr_quality {
+            args.extend(["-q:a".to_string(), quality.to_string()]);
+        }
+        if self.bit_depth.is_some() {
+            match codec {
+                AudioCodec::Flac => args.extend(["-sample_fmt".to_string(), if bit_depth == 16 { "s16" } else { "s32" }.to_string()]),
+                AudioCodec::Alac => args.extend(["-sample_fmt".to_string(), if bit_depth == 16 { "s16p" } else { "s32p" }.to_string()]),
+                _ => {}
+            }
+            if codec != AudioCodec::Pcm {
+                args.extend(["-bits_per_raw_sample".to_string(), bit_depth.to_string()]);
+            }
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            args.extend(["-ar".to_string(), sample_rate.to_string()]);
+        }
+        if let Some(channels) = self.channels {
+            args.extend(["-ac".to_string(), channels.to_string()]);
+        }
+
+        if self.keep_metadata.unwrap_or(true) {
+            args.extend(["-map_metadata".to_string(), "0".to_string()]);
+            // only these containers hold cover art
+            if has_cover_art && matches!(self.format.as_str(), "mp3" | "flac" | "m4a") {
+                args.extend([
+                    "-map".to_string(), "0:a:0".to_string(),
+                    "-map".to_string(), "0:v?".to_string(),
+                    "-c:v".to_string(), "copy".to_string(),
+                    "-disposition:v".to_string(), "attached_pic".to_string(),
+                ]);
+            } else {
+                args.push("-vn".to_string());
+            }
+        } else {
+            args.extend(["-map_metadata".to_string(), "-1".to_string(), "-vn".to_string()]);
+        }
+        Ok(args)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranscodeResult {
+    // source path of the queue item
+    pub path: String,
+    pub output_path: Option<String>,
+    // only set when the item was added to the library
+    pub output_audio_id: Option<i32>,
+    pub error: Option<String>,
+}
+
+// options or a preset must produce the `converted_type` the item asks for
+fn resolve_options(queue_item: &QueueItem) -> Result<TranscodeOptions, String> {
+    let options: TranscodeOptions = match (&queue_item.options, queue_item.preset_id) {
+        (Some(options), _) => options.clone(),
+        (None, Some(preset_id)) => find_transcode_preset(preset_id)?.options,
+        (None, None) => TranscodeOptions::for_format(&queue_item.converted_type),
+    };
+    if options.format != queue_item.converted_type {
+        return Err(format!("The options produce {} files, not {}", options.format, queue_item.converted_type));
+    }
+    Ok(options)
+}
+
+async fn transcode_single(queue_item: &QueueItem) -> Result<(PathBuf, Option<Audio>), String> {
+    let options: TranscodeOptions = resolve_options(queue_item)?;
+    let source_path: String = queue_item.path.clone();
+    let has_cover_art: bool = task::spawn_blocking(move || matches!(read_front_cover(&source_path), Ok(Some(_))))
+        .await
+        .map_err(|e| e.to_string())?;
+    let encoder_args: Vec<String> = options.ffmpeg_args(has_cover_art)?;
+
+    let base_file_name: String = format!("{}-converted_to-{}", trim_invalid_file_characters(&queue_item.title), options.format);
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), &base_file_name, &options.format)?;
+
+    let mut args: Vec<String> = vec!["-hide_banner".to_string(), "-i".to_string(), queue_item.path.clone()];
+    args.extend(encoder_args);
+    args.extend(["-y".to_string(), destination_path.to_str().unwrap().to_string()]);
+    run_ffmpeg(&args).await
+        .map_err(|e| discard_output_path(&destination_path, e))?;
+
+    if !queue_item.is_added_to_list {
+        return Ok((destination_path, None));
+    }
+
+    let created: Audio = insert_audio_entry(&NewAudio {
+        title: &queue_item.title,
+        author: &queue_item.author,
+        path: destination_path.to_str().unwrap(),
+        duration: &queue_item.duration,
+        audio_type: &options.format,
+        ..Default::default()
+    })?;
+    spawn_import_analysis(created.id, created.path.clone());
+
+    Ok((destination_path, Some(created)))
+}
+
+// items are transcoded concurrently; the results follow the queue order and a failed item does not stop the others
+#[tauri::command]
+pub async fn transcode_audio(queue_items: Vec<QueueItem>) -> Result<Vec<TranscodeResult>, String> {
+    create_audio_store_directory()?;
+
+    let handles: Vec<(String, task::JoinHandle<Result<(PathBuf, Option<Audio>), String>>)> = queue_items
+        .into_iter()
+        .map(|queue_item| (queue_item.path.clone(), task::spawn(async move { transcode_single(&queue_item).await })))
+        .collect();
+
+    let mut results: Vec<TranscodeResult> = Vec::with_capacity(handles.len());
+    for (source_path, handle) in handles {
+        // a task that panicked only fails its own item
+        let outcome = handle.await.unwrap_or_else(|e| Err(format!("Transcoding stopped unexpectedly: {}", e)));
+        results.push(match outcome {
+            Ok((output_path, created)) => TranscodeResult {
+                path: source_path,
+                output_path: Some(output_path.to_str().unwrap().to_string()),
+                output_audio_id: created.map(|created| created.id),
+                error: None,
+            },
+            Err(err) => TranscodeResult {
+                path: source_path,
+                output_path: None,
+                output_audio_id: None,
+                error: Some(err),
+            },
+        });
     }
 
-    drop(tx);
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    while let Some(result) = rx.recv().await {
-        if let Err(e) = result {
-            return Err(e);
+    #[test]
+    fn test_default_codec() {
+        assert_eq!(TranscodeOptions::for_format("wav").resolved_codec(), Ok(AudioCodec::Pcm));
+        assert_eq!(TranscodeOptions::for_format("m4a").resolved_codec(), Ok(AudioCodec::Aac));
+        assert!(TranscodeOptions::for_format("xyz").resolved_codec().is_err());
+    }
+
+    #[test]
+    fn test_mp3_320_args() {
+        let options = TranscodeOptions { bitrate_kbps: Some(320), ..TranscodeOptions::for_format("mp3") };
+        assert_eq!(
+            options.ffmpeg_args(true).unwrap(),
+            vec![
+                "-c:a", "libmp3lame", "-b:a", "320k", "-map_metadata", "0",
+                "-map", "0:a:0", "-map", "0:v?", "-c:v", "copy", "-disposition:v", "attached_pic",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_video_input_drops_the_video_stream() {
+        // a music video has a video stream but no embedded picture
+        for format in ["mp3", "flac", "m4a", "ogg"] {
+            let args = TranscodeOptions::for_format(format).ffmpeg_args(false).unwrap();
+            assert!(args.ends_with(&["-map_metadata".to_string(), "0".to_string(), "-vn".to_string()]), "{}", format);
+            assert!(!args.contains(&"-c:v".to_string()), "{}", format);
         }
+        // containers without cover art drop the picture as well
+        let args = TranscodeOptions::for_format("ogg").ffmpeg_args(true).unwrap();
+        assert_eq!(args.last().unwrap(), "-vn");
+    }
+
+    #[test]
+    fn test_wav_48k_24_bit_mono_args() {
+        let options = TranscodeOptions {
+            sample_rate: Some(48000),
+            bit_depth: Some(24),
+            channels: Some(1),
+            keep_metadata: Some(false),
+            ..TranscodeOptions::for_format("wav")
+        };
+        assert_eq!(
+            options.ffmpeg_args(false).unwrap(),
+            vec!["-c:a", "pcm_s24le", "-ar", "48000", "-ac", "1", "-map_metadata", "-1", "-vn"]
+        );
     }
 
-    for handle in handles.lock().unwrap().iter() {
-        let _ = handle;
+    #[test]
+    fn test_flac_24_bit_args() {
+        let options = TranscodeOptions { bit_depth: Some(24), ..TranscodeOptions::for_format("flac") };
+        let args = options.ffmpeg_args(false).unwrap();
+        assert_eq!(args[..6], ["-c:a", "flac", "-sample_fmt", "s32", "-bits_per_raw_sample", "24"]);
     }
 
-    Ok(())
-}
\ No newline at end of file
+    #[test]
+    fn test_partial_options_deserialize() {
+        let options: TranscodeOptions = serde_json::from_str(r#"{"format":"wav","codec":"pcm","sample_rate":48000,"bit_depth":24}"#).unwrap();
+        assert_eq!(options, TranscodeOptions { codec: Some(AudioCodec::Pcm), sample_rate: Some(48000), bit_depth: Some(24), ..TranscodeOptions::for_format("wav") });
+    }
+
+    #[test]
+    fn test_options_must_match_converted_type() {
+        let mut queue_item = QueueItem {
+            title: "clip".to_string(),
+            author: String::new(),
+            path: "/clip.wav".to_string(),
+            duration: "0:10".to_string(),
+            converted_type: "mp3".to_string(),
+            is_added_to_list: false,
+            options: Some(TranscodeOptions::for_format("flac")),
+            preset_id: None,
+        };
+        assert!(resolve_options(&queue_item).is_err());
+
+        queue_item.options = Some(TranscodeOptions { bitrate_kbps: Some(192), ..TranscodeOptions::for_format("mp3") });
+        assert_eq!(resolve_options(&queue_item).unwrap().bitrate_kbps, Some(192));
+        queue_item.options = None;
+        assert_eq!(resolve_options(&queue_item).unwrap(), TranscodeOptions::for_format("mp3"));
+    }
+
+    #[test]
+    fn test_invalid_combinations() {
+        let invalid = [
+            TranscodeOptions { codec: Some(AudioCodec::Mp3), ..TranscodeOptions::for_format("wav") },
+            TranscodeOptions { bitrate_kbps: Some(320), vbr_quality: Some(2.0), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { bitrate_kbps: Some(1411), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { bitrate_kbps: Some(320), ..TranscodeOptions::for_format("flac") },
+            TranscodeOptions { vbr_quality: Some(12.0), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { bit_depth: Some(24), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { bit_depth: Some(32), ..TranscodeOptions::for_format("flac") },
+            TranscodeOptions { sample_rate: Some(44100), ..TranscodeOptions::for_format("opus") },
+            TranscodeOptions { sample_rate: Some(96000), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { channels: Some(6), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { channels: Some(0), ..TranscodeOptions::for_format("wav") },
+        ];
+        for options in invalid {
+            assert!(options.validate().is_err(), "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_valid_combinations() {
+        let valid = [
+            TranscodeOptions { vbr_quality: Some(2.0), ..TranscodeOptions::for_format("mp3") },
+            TranscodeOptions { codec: Some(AudioCodec::Opus), bitrate_kbps: Some(96), ..TranscodeOptions::for_format("ogg") },
+            TranscodeOptions { codec: Some(AudioCodec::Alac), bit_depth: Some(24), ..TranscodeOptions::for_format("m4a") },
+            TranscodeOptions { sample_rate: Some(96000), bit_depth: Some(32), channels: Some(6), ..TranscodeOptions::for_format("wav") },
+        ];
+        for options in valid {
+            assert!(options.validate().is_ok(), "{:?}", options);
+        }
+    }
+}
@@ -1,74 +1,331 @@
-use diesel::prelude::*;
-use diesel::SqliteConnection;
-use crate::db::establish_connection;
-use crate::helper::files::trim_invalid_file_characters;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::commands::audio_commands::{find_audio, insert_audio_entry, spawn_import_analysis};
+use crate::commands::processing::effects::fade_filter;
+use crate::commands::processing::normalize::audio_file_extension;
+use crate::helper::files::{discard_output_path, next_available_path, trim_invalid_file_characters};
 pub use crate::helper::files::{create_audio_store_directory, construct_output_path};
-pub use crate::helper::tools::{seconds_to_minutes,seconds_to_hh_mm_ss};
-use crate::models::audio_model::NewAudio;
-use crate::schema::audio::dsl::*;
+pub use crate::helper::tools::{seconds_to_minutes,seconds_to_hh_mm_ss,seconds_to_hh_mm_ss_mmm};
+use crate::helper::decoder::{lossless_format, packet_boundaries, pcm_spec, LosslessFormat};
+use crate::helper::encoder::write_wav_range;
+use crate::helper::tags::copy_tags;
+use crate::helper::sidecar::run_ffmpeg;
+use crate::models::audio_model::{Audio, NewAudio};
 use crate::helper::constants::audio_store_path;
-use tauri::api::process::Command;
 
-#[tauri::command]
-pub async fn trim_single_audio(file_name:String, file_path:String, start:f64, end:f64, file_type:String) -> Result<(), String> {  
-    create_audio_store_directory()?;
-    let length = (end-start).ceil() as i32;
-    let base_file_name = format!("{}-trimmed-to-{}-sec", trim_invalid_file_characters(&file_name), length);
-    let audio_store_path = audio_store_path();
-    let mut destination_path = audio_store_path.join(format!("{}.{}",&base_file_name,&file_type)); 
-    let mut counter = 1;
-
-    while destination_path.exists() {
-        // Create a new destination path with a counter suffix
-        let new_file_name = format!("{}-{}", &base_file_name, counter);
-        destination_path = audio_store_path.join(format!("{}.{}",&new_file_name,&file_type));
-        counter += 1;
+// how far a stream copy cut may land from the requested cut for the automatic mode to choose it
+pub const STREAM_COPY_TOLERANCE: f64 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrimMode {
+    // ffmpeg re-encodes the range, cut points are exact to the millisecond
+    Reencode,
+    // ffmpeg copies the packets without re-encoding, cut points move to the nearest packet boundaries outside the range
+    StreamCopy,
+    // WAV is cut in process, exact to the sample and without loss, keeping the tags of the source.
+    // There is no FLAC encoder to write FLAC back in process, so FLAC goes through the other modes
+    Native,
+    // native for WAV, stream copy when the packet boundaries are close enough to the cut points, re-encode otherwise
+    Auto,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrimOutcome {
+    // the mode that was used, never `Auto`
+    pub mode: TrimMode,
+    pub output_path: String,
+    // actual cut points in seconds
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrimSegment {
+    // cut points in seconds, with millisecond precision
+    pub start: f64,
+    pub end: f64,
+    // title of the new entry, derived from the source title when unset
+    pub name: Option<String>,
+    // fade lengths in seconds
+    pub fade_in: Option<f64>,
+    pub fade_out: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrimResult {
+    pub output_audio_id: Option<i32>,
+    pub output_path: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TrimSegment {
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.start.is_finite() || !self.end.is_finite() || self.start < 0.0 || self.end <= self.start {
+            return Err(format!("Invalid segment {}-{}", self.start, self.end));
+        }
+        let fade_in: f64 = self.fade_in.unwrap_or(0.0);
+        let fade_out: f64 = self.fade_out.unwrap_or(0.0);
+        if fade_in < 0.0 || fade_out < 0.0 || fade_in + fade_out > self.duration() {
+            return Err(format!("Fades do not fit in segment {}-{}", self.start, self.end));
+        }
+        Ok(())
+    }
+
+    // fades for the trimmed clip, whose timestamps start at zero
+    pub fn fade_filter(&self) -> Option<String> {
+        fade_filter(self.duration(), self.fade_in, self.fade_out)
+    }
+}
+
+// cuts `start`..`end` (in seconds) out of the source file with ffmpeg, applying the optional
+// audio filter, and waits for it to finish
+pub async fn render_trim(source_path: &str, start: f64, end: f64, filter: Option<&str>, destination: &Path) -> Result<(), String> {
+    let mut args: Vec<String> = vec![
+        "-hide_banner".to_string(),
+        "-ss".to_string(), seconds_to_hh_mm_ss_mmm(start),
+        "-to".to_string(), seconds_to_hh_mm_ss_mmm(end),
+        "-i".to_string(), source_path.to_string(),
+    ];
+    if let Some(filter) = filter {
+        args.push("-af".to_string());
+        args.push(filter.to_string());
     }
+    args.push("-y".to_string());
+    args.push(destination.to_str().unwrap().to_string());
+
+    run_ffmpeg(&args).await?;
+    Ok(())
+}
+
+// widens `start`..`end` to the packet boundaries around it, the range a stream copy actually produces
+pub fn snap_to_boundaries(boundaries: &[f64], start: f64, end: f64) -> (f64, f64) {
+    let snapped_start: f64 = boundaries.iter().copied().filter(|b| *b <= start).fold(boundaries.first().copied().unwrap_or(start), f64::max);
+    let snapped_end: f64 = boundaries.iter().copied().filter(|b| *b >= end).fold(boundaries.last().copied().unwrap_or(end), f64::min);
+    (snapped_start, snapped_end)
+}
+
+async fn stream_copy_range(source_path: &str, start: f64, end: f64) -> Result<(f64, f64), String> {
+    let path: String = source_path.to_string();
+    let boundaries: Vec<f64> = tokio::task::spawn_blocking(move || packet_boundaries(&path))
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok(snap_to_boundaries(&boundaries, start, end))
+}
 
-    let start_as_hh_mm_ss = seconds_to_hh_mm_ss(start as u64);
-    let end_as_hh_mm_ss = seconds_to_hh_mm_ss(end as u64);
-    
-    let args = vec![
-      "-ss", start_as_hh_mm_ss.as_str(),
-      "-to", end_as_hh_mm_ss.as_str(),
-      "-i", &file_path,
-      &destination_path.to_str().unwrap(),
+async fn render_stream_copy(source_path: &str, start: f64, end: f64, destination: &Path) -> Result<(), String> {
+    let args: Vec<String> = vec![
+        "-hide_banner".to_string(),
+        "-ss".to_string(), seconds_to_hh_mm_ss_mmm(start),
+        "-to".to_string(), seconds_to_hh_mm_ss_mmm(end),
+        "-i".to_string(), source_path.to_string(),
+        "-c".to_string(), "copy".to_string(),
+        "-y".to_string(),
+        destination.to_str().unwrap().to_string(),
     ];
 
-    let command = if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" };
-    Command::new_sidecar(command)
-        .expect("failed to create `my-sidecar` binary command")
-        .args(&args)
-        .spawn()
-        .expect("Failed to spawn sidecar");
-
-    let timeout_duration = std::time::Duration::from_secs(120);
-    let start_time = std::time::Instant::now();
-    // Wait for the output path to exist
-    while !std::path::Path::new(destination_path.to_str().unwrap()).exists() {
-        if start_time.elapsed() > timeout_duration {
-            panic!("Timeout waiting for output file to exist");
+    run_ffmpeg(&args).await?;
+    Ok(())
+}
+
+// cuts the exact frame range out of a WAV file in process and writes it as WAV with the tags of the source.
+// The cut points returned are those of the frames actually written
+async fn render_native(source_path: &str, start: f64, end: f64, destination: &Path) -> Result<(f64, f64), String> {
+    let path: String = source_path.to_string();
+    let output: PathBuf = destination.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<(f64, f64), String> {
+        let sample_rate: f64 = pcm_spec(&path)?.sample_rate as f64;
+        let start_frame: u64 = (start * sample_rate).round() as u64;
+        let end_frame: u64 = (end * sample_rate).round() as u64;
+        let frames: u64 = write_wav_range(&path, start_frame, end_frame, &output)?;
+        copy_tags(&path, output.to_str().unwrap())?;
+        Ok((start_frame as f64 / sample_rate, (start_frame + frames) as f64 / sample_rate))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// renders `start`..`end` of the source into the audio store as `<base_file_name>.<extension>`
+// with the given mode; native trimming keeps the container of the source instead
+pub async fn render_trim_with_mode(source_path: &str, start: f64, end: f64, mode: TrimMode, base_file_name: &str, extension: &str) -> Result<TrimOutcome, String> {
+    create_audio_store_directory()?;
+
+    let path: String = source_path.to_string();
+    let is_native: bool = tokio::task::spawn_blocking(move || lossless_format(&path) == Some(LosslessFormat::Wav))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut copy_range: Option<(f64, f64)> = None;
+    let mode: TrimMode = match (mode, is_native) {
+        (TrimMode::Auto, true) => TrimMode::Native,
+        (TrimMode::Auto, false) => {
+            let (snapped_start, snapped_end) = stream_copy_range(source_path, start, end).await?;
+            copy_range = Some((snapped_start, snapped_end));
+            if (snapped_start - start).abs() <= STREAM_COPY_TOLERANCE && (snapped_end - end).abs() <= STREAM_COPY_TOLERANCE {
+                TrimMode::StreamCopy
+            } else {
+                TrimMode::Reencode
+            }
+        }
+        (mode, _) => mode,
+    };
+
+    let extension: &str = match (mode, is_native) {
+        (TrimMode::Native, true) => LosslessFormat::Wav.extension(),
+        (TrimMode::Native, false) => return Err("Native trimming only supports integer PCM WAV files".to_string()),
+        _ => extension,
+    };
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), base_file_name, extension)?;
+
+    let rendered: Result<(f64, f64), String> = async {
+        match mode {
+            TrimMode::StreamCopy => {
+                let (snapped_start, snapped_end) = match copy_range {
+                    Some(range) => range,
+                    None => stream_copy_range(source_path, start, end).await?,
+                };
+                render_stream_copy(source_path, snapped_start, snapped_end, &destination_path).await?;
+                Ok((snapped_start, snapped_end))
+            }
+            TrimMode::Native => render_native(source_path, start, end, &destination_path).await,
+            _ => {
+                render_trim(source_path, start, end, None, &destination_path).await?;
+                // ffmpeg cuts to the millisecond
+                Ok(((start * 1000.0).round() / 1000.0, (end * 1000.0).round() / 1000.0))
+            }
         }
-        std::thread::sleep(std::time::Duration::from_secs(1));
+    }.await;
+    let (actual_start, actual_end) = rendered.map_err(|e| discard_output_path(&destination_path, e))?;
+
+    Ok(TrimOutcome {
+        mode,
+        output_path: destination_path.to_str().unwrap().to_string(),
+        start: actual_start,
+        end: actual_end,
+    })
+}
+
+// trims the source entry into a new entry in the audio store that is linked back to the source
+pub async fn trim_to_new_entry(source: &Audio, start: f64, end: f64, filter: Option<&str>, base_file_name: &str) -> Result<Audio, String> {
+    trim_to_titled_entry(source, start, end, filter, base_file_name, base_file_name).await
+}
+
+// like `trim_to_new_entry`, with a title other than the file name
+pub async fn trim_to_titled_entry(source: &Audio, start: f64, end: f64, filter: Option<&str>, base_file_name: &str, title: &str) -> Result<Audio, String> {
+    create_audio_store_directory()?;
+    let extension: String = audio_file_extension(source);
+    let destination_path: PathBuf = next_available_path(&audio_store_path(), base_file_name, &extension)?;
+    render_trim(&source.path, start, end, filter, &destination_path).await
+        .map_err(|e| discard_output_path(&destination_path, e))?;
+
+    insert_audio_entry(&NewAudio {
+        title,
+        author: &source.author,
+        path: destination_path.to_str().unwrap(),
+        duration: &seconds_to_minutes((end - start).round() as u64),
+        audio_type: &source.audio_type,
+        album: source.album.as_deref(),
+        genre: source.genre.as_deref(),
+        comment: source.comment.as_deref(),
+        artwork_hash: source.artwork_hash.as_deref(),
+        source_audio_id: Some(source.id),
+        ..Default::default()
+    })
+}
+
+// `mode` defaults to re-encoding. Returns the mode that was used and the actual cut points
+#[tauri::command]
+pub async fn trim_single_audio(file_name:String, file_path:String, start:f64, end:f64, file_type:String, mode: Option<TrimMode>) -> Result<TrimOutcome, String> {  
+    if !start.is_finite() || !end.is_finite() || start < 0.0 || end <= start {
+        return Err(format!("Invalid trim range {}-{}", start, end));
     }
-  
-    let mut connection: SqliteConnection = establish_connection();
-    let new_audio: NewAudio<'_> = NewAudio {
+    let length = (end-start).ceil() as i32;
+    let base_file_name = format!("{}-trimmed-to-{}-sec", trim_invalid_file_characters(&file_name), length);
+
+    let outcome: TrimOutcome = render_trim_with_mode(&file_path, start, end, mode.unwrap_or(TrimMode::Reencode), &base_file_name, &file_type).await?;
+
+    // native trimming keeps the container of the source, which may differ from `file_type`
+    let output_path: &Path = Path::new(&outcome.output_path);
+    let audio_type: &str = output_path.extension().and_then(|extension| extension.to_str()).unwrap_or(&file_type);
+    insert_audio_entry(&NewAudio {
         title: &base_file_name,
         author: "Unknown",
-        path: &destination_path.to_str().unwrap(),
-        duration: &seconds_to_minutes(length as u64),
-        audio_type: &file_type,
-    };
+        path: &outcome.output_path,
+        duration: &seconds_to_minutes((outcome.end - outcome.start).round() as u64),
+        audio_type,
+        ..Default::default()
+    }).map_err(|e| discard_output_path(output_path, e))?;
+    Ok(outcome)
+}
+
+// renders every segment of the source in one job. All segments are validated before anything is
+// rendered; a segment that fails to render is reported without stopping the others
+#[tauri::command(async)]
+pub async fn trim_segments(audio_id: i32, segments: Vec<TrimSegment>) -> Result<Vec<TrimResult>, String> {
+    let source: Audio = find_audio(audio_id)?;
+    for segment in &segments {
+        segment.validate()?;
+    }
 
-    let result: Result<usize, diesel::result::Error> = diesel::insert_into(audio)
-        .values(&new_audio)
-        .execute(&mut connection);
+    let base_file_name: String = trim_invalid_file_characters(&source.title);
+    let mut results: Vec<TrimResult> = Vec::with_capacity(segments.len());
+    for (index, segment) in segments.iter().enumerate() {
+        let segment_name: String = match &segment.name {
+            Some(name) => trim_invalid_file_characters(name),
+            None => format!("{}-segment-{}", base_file_name, index + 1),
+        };
+        let filter: Option<String> = segment.fade_filter();
+        let result: Result<Audio, String> = trim_to_new_entry(&source, segment.start, segment.end, filter.as_deref(), &segment_name).await;
 
-    match result{
-        Ok(_) => Ok(()),
-        Err(e) => {
-            return Err(format!("Error: Could not add audio entry to database: {}", e));
-        }
+        results.push(match result {
+            Ok(created) => {
+                spawn_import_analysis(created.id, created.path.clone());
+                TrimResult { output_audio_id: Some(created.id), output_path: Some(created.path), error: None }
+            }
+            Err(err) => TrimResult { output_audio_id: None, output_path: None, error: Some(err) },
+        });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start: f64, end: f64, fade_in: Option<f64>, fade_out: Option<f64>) -> TrimSegment {
+        TrimSegment { start, end, name: None, fade_in, fade_out }
+    }
+
+    #[test]
+    fn test_validate_segment() {
+        assert!(segment(1.25, 3.5, None, None).validate().is_ok());
+        assert!(segment(1.25, 3.5, Some(1.0), Some(1.25)).validate().is_ok());
+        assert!(segment(3.5, 1.25, None, None).validate().is_err());
+        assert!(segment(-1.0, 1.0, None, None).validate().is_err());
+        assert!(segment(0.0, f64::NAN, None, None).validate().is_err());
+        // fades longer than the segment
+        assert!(segment(0.0, 2.0, Some(1.5), Some(1.0)).validate().is_err());
+    }
+
+    #[test]
+    fn test_snap_to_boundaries() {
+        // 1152 sample MP3 frames at 44.1 kHz
+        let frame: f64 = 1152.0 / 44100.0;
+        let boundaries: Vec<f64> = (0..=100).map(|i| i as f64 * frame).collect();
+
+        let (start, end) = snap_to_boundaries(&boundaries, 1.0, 2.0);
+        assert_eq!(start, 38.0 * frame);
+        assert_eq!(end, 77.0 * frame);
+        assert!(start <= 1.0 && end >= 2.0);
+
+        // cut points on boundaries stay where they are
+        assert_eq!(snap_to_boundaries(&boundaries, 10.0 * frame, 20.0 * frame), (10.0 * frame, 20.0 * frame));
+        // past the end of the stream
+        assert_eq!(snap_to_boundaries(&boundaries, 1.0, 1000.0).1, 100.0 * frame);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,97 @@
+use diesel::prelude::*;
+use crate::commands::processing::recipe::{validate_steps, RecipeDefinition, RecipeStep};
+use crate::models::recipe_model::{NewRecipe, Recipe};
+use crate::db::establish_connection;
+
+fn to_definition(stored: Recipe) -> Result<RecipeDefinition, String> {
+  let steps: Vec<RecipeStep> = serde_json::from_str(&stored.steps)
+    .map_err(|e| format!("Invalid steps in recipe {}: {}", stored.name, e))?;
+  Ok(RecipeDefinition { id: stored.id, name: stored.name, steps })
+}
+
+fn serialize_steps(steps: &[RecipeStep]) -> Result<String, String> {
+  validate_steps(steps)?;
+  serde_json::to_string(steps).map_err(|e| format!("Unable to serialize recipe steps: {}", e))
+}
+
+pub fn find_recipe(recipe_id_arg: i32) -> Result<RecipeDefinition, String> {
+  use crate::schema::recipe::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let stored: Recipe = recipe.find(recipe_id_arg)
+    .first::<Recipe>(&mut connection)
+    .map_err(|e| format!("Error finding recipe {}: {}", recipe_id_arg, e))?;
+  to_definition(stored)
+}
+
+#[tauri::command]
+pub fn create_recipe(name_arg: String, steps_arg: Vec<RecipeStep>) -> Result<RecipeDefinition, String> {
+  use crate::schema::recipe::dsl::*;
+
+  let serialized: String = serialize_steps(&steps_arg)?;
+  let mut connection: SqliteConnection = establish_connection();
+
+  let result: Result<Recipe, diesel::result::Error> = diesel::insert_into(recipe)
+    .values(&NewRecipe { name: &name_arg, steps: &serialized })
+    .get_result::<Recipe>(&mut connection);
+
+  match result {
+    Ok(stored) => to_definition(stored),
+    Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => {
+        Err(format!("Error: A recipe named {} already exists", name_arg))
+    }
+    Err(err) => Err(format!("Error: {}", err)),
+  }
+}
+
+#[tauri::command]
+pub fn get_all_recipes() -> Result<Vec<RecipeDefinition>, String> {
+  use crate::schema::recipe::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let stored: Vec<Recipe> = recipe.order(name.asc())
+    .load::<Recipe>(&mut connection)
+    .map_err(|e| format!("Error querying recipes: {}", e))?;
+  stored.into_iter().map(to_definition).collect()
+}
+
+#[tauri::command]
+pub fn update_recipe(id_arg: i32, name_arg: Option<String>, steps_arg: Option<Vec<RecipeStep>>) -> Result<RecipeDefinition, String> {
+  use crate::schema::recipe::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let current_recipe: Recipe = recipe.find(id_arg)
+    .first::<Recipe>(&mut connection)
+    .map_err(|e| format!("Error finding recipe {}: {}", id_arg, e))?;
+
+  let updated_recipe: Recipe = Recipe {
+    name: name_arg.unwrap_or(current_recipe.name),
+    steps: match steps_arg {
+      Some(new_steps) => serialize_steps(&new_steps)?,
+      None => current_recipe.steps,
+    },
+    ..current_recipe
+  };
+
+  diesel::update(recipe.find(id_arg))
+    .set(&updated_recipe)
+    .execute(&mut connection)
+    .map_err(|e| format!("Error updating recipe: {}", e))?;
+
+  to_definition(updated_recipe)
+}
+
+#[tauri::command]
+pub fn delete_recipe(id_arg: i32) -> Result<(), String> {
+  use crate::schema::recipe::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::delete(recipe.find(id_arg))
+    .execute(&mut connection)
+    .map_err(|e| format!("Error deleting recipe: {}", e))?;
+  Ok(())
+}
@@ -0,0 +1,95 @@
+use diesel::prelude::*;
+use crate::db::establish_connection;
+use crate::helper::artwork::{artwork_file, embeddable_image};
+use crate::helper::files::read_file_to_buffer;
+use crate::helper::tags::{read_tag_fields, write_tag_fields, TagFields};
+use crate::models::audio_model::Audio;
+
+// the artwork linked to the entry, in a format every tag type can hold
+pub fn linked_cover_art(audio_entry: &Audio) -> Result<Option<Vec<u8>>, String> {
+  match &audio_entry.artwork_hash {
+    Some(hash) => {
+      let bytes: Vec<u8> = std::fs::read(artwork_file(hash, None)?)
+        .map_err(|e| format!("Unable to read artwork: {}", e))?;
+      Ok(Some(embeddable_image(bytes)?))
+    }
+    None => Ok(None),
+  }
+}
+
+// the catalog metadata of an entry as tag fields
+pub fn audio_tag_fields(audio_entry: &Audio) -> TagFields {
+  TagFields {
+    title: Some(audio_entry.title.clone()),
+    // "Unknown" is the placeholder used on import, do not write it into the file
+    artist: match audio_entry.author.as_str() {
+      "Unknown" | "" => None,
+      author => Some(author.to_string()),
+    },
+    album: audio_entry.album.clone(),
+    genre: audio_entry.genre.clone(),
+    comment: audio_entry.comment.clone(),
+    recording_date: None,
+  }
+}
+
+// writes the catalog metadata of an audio entry into the tags of its file in the audio store.
+// without an explicit cover, the artwork linked to the entry (if any) is embedded
+pub fn write_audio_tags(audio_entry: &Audio, cover_art: Option<&[u8]>) -> Result<(), String> {
+  let linked_artwork: Option<Vec<u8>> = match cover_art {
+    Some(_) => None,
+    None => linked_cover_art(audio_entry)?,
+  };
+  let cover_art: Option<&[u8]> = cover_art.or(linked_artwork.as_deref());
+
+  write_tag_fields(&audio_entry.path, &audio_tag_fields(audio_entry), cover_art)
+}
+
+#[tauri::command(async)]
+pub fn write_tags(audio_id_arg: i32, cover_art_path: Option<String>) -> Result<(), String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let selected_audio: Audio = audio
+    .find(audio_id_arg)
+    .first::<Audio>(&mut connection)
+    .map_err(|e| format!("Error finding audio: {}", e))?;
+
+  let cover_art: Option<Vec<u8>> = match cover_art_path {
+    Some(cover_path) => Some(read_file_to_buffer(&cover_path)?),
+    None => None,
+  };
+
+  write_audio_tags(&selected_audio, cover_art.as_deref())
+}
+
+#[tauri::command(async)]
+pub fn sync_tags_from_file(audio_id_arg: i32) -> Result<Audio, String> {
+  use crate::schema::audio::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let current_audio: Audio = audio
+    .find(audio_id_arg)
+    .first::<Audio>(&mut connection)
+    .map_err(|e| format!("Error finding audio: {}", e))?;
+
+  let fields: TagFields = read_tag_fields(&current_audio.path)?;
+
+  let synced_audio: Audio = Audio {
+    title: fields.title.unwrap_or(current_audio.title),
+    author: fields.artist.unwrap_or(current_audio.author),
+    album: fields.album.or(current_audio.album),
+    genre: fields.genre.or(current_audio.genre),
+    comment: fields.comment.or(current_audio.comment),
+    ..current_audio
+  };
+
+  diesel::update(audio.find(audio_id_arg))
+    .set(&synced_audio)
+    .execute(&mut connection)
+    .map_err(|e| format!("Error updating audio entry: {}", e))?;
+
+  Ok(synced_audio)
+}
@@ -0,0 +1,105 @@
+use diesel::prelude::*;
+use serde::{Serialize, Deserialize};
+use crate::commands::processing::transcode::TranscodeOptions;
+use crate::models::transcode_preset_model::{NewTranscodePreset, TranscodePreset};
+use crate::db::establish_connection;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TranscodePresetDefinition {
+  pub id: i32,
+  pub name: String,
+  pub options: TranscodeOptions,
+}
+
+fn to_definition(stored: TranscodePreset) -> Result<TranscodePresetDefinition, String> {
+  let options: TranscodeOptions = serde_json::from_str(&stored.options)
+    .map_err(|e| format!("Invalid options in transcode preset {}: {}", stored.name, e))?;
+  Ok(TranscodePresetDefinition { id: stored.id, name: stored.name, options })
+}
+
+fn serialize_options(options: &TranscodeOptions) -> Result<String, String> {
+  options.validate()?;
+  serde_json::to_string(options).map_err(|e| format!("Unable to serialize transcode options: {}", e))
+}
+
+pub fn find_transcode_preset(preset_id_arg: i32) -> Result<TranscodePresetDefinition, String> {
+  use crate::schema::transcode_preset::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let stored: TranscodePreset = transcode_preset.find(preset_id_arg)
+    .first::<TranscodePreset>(&mut connection)
+    .map_err(|e| format!("Error finding transcode preset {}: {}", preset_id_arg, e))?;
+  to_definition(stored)
+}
+
+#[tauri::command]
+pub fn create_transcode_preset(name_arg: String, options_arg: TranscodeOptions) -> Result<TranscodePresetDefinition, String> {
+  use crate::schema::transcode_preset::dsl::*;
+
+  let serialized: String = serialize_options(&options_arg)?;
+  let mut connection: SqliteConnection = establish_connection();
+
+  let result: Result<TranscodePreset, diesel::result::Error> = diesel::insert_into(transcode_preset)
+    .values(&NewTranscodePreset { name: &name_arg, options: &serialized })
+    .get_result::<TranscodePreset>(&mut connection);
+
+  match result {
+    Ok(stored) => to_definition(stored),
+    Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => {
+        Err(format!("Error: A transcode preset named {} already exists", name_arg))
+    }
+    Err(err) => Err(format!("Error: {}", err)),
+  }
+}
+
+#[tauri::command]
+pub fn get_all_transcode_presets() -> Result<Vec<TranscodePresetDefinition>, String> {
+  use crate::schema::transcode_preset::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let stored: Vec<TranscodePreset> = transcode_preset.order(name.asc())
+    .load::<TranscodePreset>(&mut connection)
+    .map_err(|e| format!("Error querying transcode presets: {}", e))?;
+  stored.into_iter().map(to_definition).collect()
+}
+
+#[tauri::command]
+pub fn update_transcode_preset(id_arg: i32, name_arg: Option<String>, options_arg: Option<TranscodeOptions>) -> Result<TranscodePresetDefinition, String> {
+  use crate::schema::transcode_preset::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  let current_preset: TranscodePreset = transcode_preset.find(id_arg)
+    .first::<TranscodePreset>(&mut connection)
+    .map_err(|e| format!("Error finding transcode preset {}: {}", id_arg, e))?;
+
+  let updated_preset: TranscodePreset = TranscodePreset {
+    name: name_arg.unwrap_or(current_preset.name),
+    options: match options_arg {
+      Some(new_options) => serialize_options(&new_options)?,
+      None => current_preset.options,
+    },
+    ..current_preset
+  };
+
+  diesel::update(transcode_preset.find(id_arg))
+    .set(&updated_preset)
+    .execute(&mut connection)
+    .map_err(|e| format!("Error updating transcode preset: {}", e))?;
+
+  to_definition(updated_preset)
+}
+
+#[tauri::command]
+pub fn delete_transcode_preset(id_arg: i32) -> Result<(), String> {
+  use crate::schema::transcode_preset::dsl::*;
+
+  let mut connection: SqliteConnection = establish_connection();
+
+  diesel::delete(transcode_preset.find(id_arg))
+    .execute(&mut connection)
+    .map_err(|e| format!("Error deleting transcode preset: {}", e))?;
+  Ok(())
+}
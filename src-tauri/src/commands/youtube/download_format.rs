@@ -0,0 +1,202 @@
+use serde::{Serialize, Deserialize};
+use crate::helper::tools::seconds_to_hh_mm_ss_mmm;
+
+// downloads larger than this are refused unless the options set another limit
+pub const DEFAULT_MAX_FILESIZE_MB: u64 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadCodec {
+    Opus,
+    M4a,
+    Mp3,
+    Wav,
+    Flac,
+}
+
+impl DownloadCodec {
+    // value of yt-dlp's --audio-format, which is also the extension of the file it writes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadCodec::Opus => "opus",
+            DownloadCodec::M4a => "m4a",
+            DownloadCodec::Mp3 => "mp3",
+            DownloadCodec::Wav => "wav",
+            DownloadCodec::Flac => "flac",
+        }
+    }
+
+    pub fn is_lossless(&self) -> bool {
+        matches!(self, DownloadCodec::Wav | DownloadCodec::Flac)
+    }
+}
+
+// what yt-dlp keeps of a video. Without options the audio is converted to mp3 like before
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DownloadFormat {
+    // store the best audio stream as it is instead of converting it
+    #[serde(default)]
+    pub keep_original: bool,
+    // target codec when converting, defaults to mp3
+    pub codec: Option<DownloadCodec>,
+    // constant bitrate of lossy codecs
+    pub bitrate_kbps: Option<u32>,
+    // VBR quality of lossy codecs, 0 (best) to 10 (worst)
+    pub vbr_quality: Option<u8>,
+    pub max_filesize_mb: Option<u64>,
+}
+
+impl DownloadFormat {
+    pub fn resolved_codec(&self) -> DownloadCodec {
+        self.codec.unwrap_or(DownloadCodec::Mp3)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_filesize_mb == Some(0) {
+            return Err("The maximum file size must be at least 1 MB".to_string());
+        }
+        if self.keep_original {
+            if self.codec.is_some() || self.bitrate_kbps.is_some() || self.vbr_quality.is_some() {
+                return Err("Codec and quality settings need the audio to be converted".to_string());
+            }
+            return Ok(());
+        }
+
+        let codec: DownloadCodec = self.resolved_codec();
+        if self.bitrate_kbps.is_some() && self.vbr_quality.is_some() {
+            return Err("Choose either a bitrate or a VBR quality".to_string());
+        }
+        if codec.is_lossless() && (self.bitrate_kbps.is_some() || self.vbr_quality.is_some()) {
+            return Err(format!("{:?} is lossless and has no quality setting", codec));
+        }
+        if let Some(bitrate) = self.bitrate_kbps {
+            if !(8..=512).contains(&bitrate) {
+                return Err("Bitrate must be between 8 and 512 kbps".to_string());
+            }
+        }
+        if let Some(quality) = self.vbr_quality {
+            if quality > 10 {
+                return Err(format!("Invalid VBR quality {}, use 0 (best) to 10 (worst)", quality));
+            }
+        }
+        Ok(())
+    }
+
+    // yt-dlp arguments selecting the audio and how it is post-processed
+    pub fn yt_dlp_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-x".to_string(),
+            "--max-filesize".to_string(), format!("{}M", self.max_filesize_mb.unwrap_or(DEFAULT_MAX_FILESIZE_MB)),
+        ];
+        // without --audio-format yt-dlp keeps the codec of the downloaded stream
+        if self.keep_original {
+            return args;
+        }
+
+        args.extend(["--audio-format".to_string(), self.resolved_codec().as_str().to_string()]);
+        if let Some(bitrate) = self.bitrate_kbps {
+            args.extend(["--audio-quality".to_string(), format!("{}K", bitrate)]);
+        } else if let Some(quality) = self.vbr_quality {
+            args.extend(["--audio-quality".to_string(), quality.to_string()]);
+        }
+        args
+    }
+}
+
+// checks a requested section against the length of the video, when it is known
+pub fn validate_section(start: Option<f64>, end: Option<f64>, duration_seconds: Option<u64>) -> Result<(), String> {
+    let start_secs: f64 = start.unwrap_or(0.0);
+    if !start_secs.is_finite() || start_secs < 0.0 {
+        return Err(format!("Invalid section start {}", start_secs));
+    }
+    if let Some(end) = end {
+        if !end.is_finite() || end <= start_secs {
+            return Err(format!("Section end {} must come after its start {}", end, start_secs));
+        }
+    }
+    if let Some(duration) = duration_seconds {
+        if start_secs >= duration as f64 {
+            return Err(format!("Section start {} is past the end of the video ({} s)", start_secs, duration));
+        }
+    }
+    Ok(())
+}
+
+// yt-dlp arguments fetching only `start` to `end` seconds of the video; none when no section is set
+pub fn section_args(start: Option<f64>, end: Option<f64>) -> Vec<String> {
+    if start.is_none() && end.is_none() {
+        return Vec::new();
+    }
+    let end: String = end.map(seconds_to_hh_mm_ss_mmm).unwrap_or("inf".to_string());
+    vec![
+        "--download-sections".to_string(), format!("*{}-{}", seconds_to_hh_mm_ss_mmm(start.unwrap_or(0.0)), end),
+        // cut at the exact offsets rather than the nearest keyframes
+        "--force-keyframes-at-cuts".to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_converts_to_mp3() {
+        let format = DownloadFormat::default();
+        assert!(format.validate().is_ok());
+        assert_eq!(format.yt_dlp_args(), vec!["-x", "--max-filesize", "500M", "--audio-format", "mp3"]);
+    }
+
+    #[test]
+    fn test_yt_dlp_args() {
+        let opus = DownloadFormat { codec: Some(DownloadCodec::Opus), bitrate_kbps: Some(160), max_filesize_mb: Some(100), ..Default::default() };
+        assert_eq!(opus.yt_dlp_args(), vec!["-x", "--max-filesize", "100M", "--audio-format", "opus", "--audio-quality", "160K"]);
+
+        let mp3 = DownloadFormat { vbr_quality: Some(2), ..Default::default() };
+        assert_eq!(mp3.yt_dlp_args(), vec!["-x", "--max-filesize", "500M", "--audio-format", "mp3", "--audio-quality", "2"]);
+
+        let original = DownloadFormat { keep_original: true, ..Default::default() };
+        assert_eq!(original.yt_dlp_args(), vec!["-x", "--max-filesize", "500M"]);
+    }
+
+    #[test]
+    fn test_validate_rejects_invalid_combinations() {
+        let invalid = [
+            DownloadFormat { keep_original: true, codec: Some(DownloadCodec::Flac), ..Default::default() },
+            DownloadFormat { bitrate_kbps: Some(192), vbr_quality: Some(2), ..Default::default() },
+            DownloadFormat { codec: Some(DownloadCodec::Flac), bitrate_kbps: Some(320), ..Default::default() },
+            DownloadFormat { bitrate_kbps: Some(2000), ..Default::default() },
+            DownloadFormat { vbr_quality: Some(11), ..Default::default() },
+            DownloadFormat { max_filesize_mb: Some(0), ..Default::default() },
+        ];
+        for format in invalid {
+            assert!(format.validate().is_err(), "{:?} should be rejected", format);
+        }
+        assert!(DownloadFormat { codec: Some(DownloadCodec::Wav), ..Default::default() }.validate().is_ok());
+    }
+
+    #[test]
+    fn test_section_args() {
+        assert!(section_args(None, None).is_empty());
+        assert_eq!(section_args(Some(3723.5), Some(3743.5)), vec!["--download-sections", "*01:02:03.500-01:02:23.500", "--force-keyframes-at-cuts"]);
+        assert_eq!(section_args(Some(20.0), None), vec!["--download-sections", "*00:00:20.000-inf", "--force-keyframes-at-cuts"]);
+        assert_eq!(section_args(None, Some(20.0)), vec!["--download-sections", "*00:00:00.000-00:00:20.000", "--force-keyframes-at-cuts"]);
+    }
+
+    #[test]
+    fn test_validate_section() {
+        assert!(validate_section(Some(10.0), Some(30.0), Some(7200)).is_ok());
+        assert!(validate_section(None, Some(30.0), None).is_ok());
+        assert!(validate_section(Some(30.0), Some(10.0), None).is_err());
+        assert!(validate_section(Some(-1.0), None, None).is_err());
+        assert!(validate_section(Some(7200.0), None, Some(7200)).is_err());
+        assert!(validate_section(Some(f64::NAN), None, None).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_options() {
+        let format: DownloadFormat = serde_json::from_str(r#"{"codec": "m4a", "max_filesize_mb": 50}"#).unwrap();
+        assert_eq!(format.resolved_codec(), DownloadCodec::M4a);
+        assert!(!format.keep_original);
+        assert_eq!(format.max_filesize_mb, Some(50));
+    }
+}
@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use tauri::api::process::{CommandChild, CommandEvent};
+use crate::helper::sidecar::{spawn_sidecar, stderr_tail, yt_dlp_sidecar};
+use crate::commands::youtube::download_format::DownloadFormat;
+
+// yt-dlp prints this prefix followed by the fields of `PROGRESS_TEMPLATE`
+const PROGRESS_PREFIX: &str = "chamber-progress";
+const PROGRESS_TEMPLATE: &str = "download:chamber-progress %(progress.downloaded_bytes)s %(progress.total_bytes)s %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+const BACKOFF_BASE_SECS: u64 = 2;
+const BACKOFF_MAX_SECS: u64 = 60;
+
+static NEXT_DOWNLOAD_ID: AtomicU32 = AtomicU32::new(1);
+
+lazy_static::lazy_static! {
+    static ref ACTIVE_DOWNLOADS: Mutex<HashMap<u32, ActiveDownload>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Default)]
+struct ActiveDownload {
+    // the running yt-dlp process, unset between attempts
+    child: Option<CommandChild>,
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadOptions {
+    // limit for a single attempt, unset means no limit
+    pub timeout_secs: Option<u64>,
+    // an attempt is abandoned when yt-dlp prints nothing for this long, defaults to 120 s
+    pub stall_timeout_secs: Option<u64>,
+    // retries after transient failures, defaults to 3
+    pub max_retries: Option<u32>,
+    #[serde(default)]
+    pub format: DownloadFormat,
+    // also create one entry per chapter of the video
+    #[serde(default)]
+    pub split_chapters: bool,
+    // collect the chapter entries into a new playlist named after the video
+    #[serde(default)]
+    pub chapter_playlist: bool,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            timeout_secs: None,
+            stall_timeout_secs: Some(120),
+            max_retries: Some(3),
+            format: DownloadFormat::default(),
+            split_chapters: false,
+            chapter_playlist: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadStatus {
+    Queued,
+    Downloading,
+    Retrying,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+// payload of the "download-progress" event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadProgress {
+    pub id: u32,
+    pub url: String,
+    pub status: DownloadStatus,
+    pub attempt: u32,
+    pub percent: Option<f64>,
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    // bytes per second
+    pub speed: Option<f64>,
+    pub eta_secs: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl DownloadProgress {
+    pub fn new(id: u32, url: &str, status: DownloadStatus) -> Self {
+        DownloadProgress {
+            id,
+            url: url.to_string(),
+            status,
+            attempt: 0,
+            percent: None,
+            downloaded_bytes: None,
+            total_bytes: None,
+            speed: None,
+            eta_secs: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct YtDlpProgress {
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    pub speed: Option<f64>,
+    pub eta_secs: Option<u64>,
+}
+
+impl YtDlpProgress {
+    pub fn percent(&self) -> Option<f64> {
+        match (self.downloaded_bytes, self.total_bytes) {
+            (Some(downloaded), Some(total)) if total > 0 => Some((downloaded as f64 / total as f64 * 100.0).min(100.0)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DownloadError {
+    Cancelled,
+    // worth another attempt: network errors, throttling, timeouts
+    Transient(String),
+    Fatal(String),
+}
+
+impl DownloadError {
+    pub fn message(&self) -> String {
+        match self {
+            DownloadError::Cancelled => "Download cancelled".to_string(),
+            DownloadError::Transient(message) | DownloadError::Fatal(message) => message.clone(),
+        }
+    }
+}
+
+// yt-dlp writes "NA" for values it does not know
+fn parse_field<T: std::str::FromStr>(field: Option<&str>) -> Option<T> {
+    field.filter(|value| *value != "NA" && *value != "None").and_then(|value| value.parse().ok())
+}
+
+// reads a line printed through `PROGRESS_TEMPLATE`
+pub fn parse_progress_line(line: &str) -> Option<YtDlpProgress> {
+    let mut fields = line.trim().strip_prefix(PROGRESS_PREFIX)?.split_whitespace();
+    let downloaded_bytes: Option<u64> = parse_field::<f64>(fields.next()).map(|bytes| bytes as u64);
+    let total_bytes: Option<u64> = parse_field::<f64>(fields.next()).map(|bytes| bytes as u64);
+    let total_estimate: Option<u64> = parse_field::<f64>(fields.next()).map(|bytes| bytes as u64);
+    Some(YtDlpProgress {
+        downloaded_bytes,
+        total_bytes: total_bytes.or(total_estimate),
+        speed: parse_field(fields.next()),
+        eta_secs: parse_field::<f64>(fields.next()).map(|eta| eta as u64),
+    })
+}
+
+// whether a failed attempt is worth repeating, judged from what yt-dlp wrote to stderr
+pub fn is_transient_error(stderr: &str) -> bool {
+    const PERMANENT: [&str; 7] = [
+        "Video unavailable",
+        "Private video",
+        "This video is not available",
+        "Sign in to confirm your age",
+        "members-only",
+        "Unsupported URL",
+        "larger than max-filesize",
+    ];
+    const TRANSIENT: [&str; 10] = [
+        "HTTP Error 429",
+        "HTTP Error 500",
+        "HTTP Error 502",
+        "HTTP Error 503",
+        "HTTP Error 504",
+        "timed out",
+        "Connection reset",
+        "Temporary failure in name resolution",
+        "IncompleteRead",
+        "Unable to download",
+    ];
+    if PERMANENT.iter().any(|pattern| stderr.contains(pattern)) {
+        return false;
+    }
+    TRANSIENT.iter().any(|pattern| stderr.contains(pattern))
+}
+
+// 2 s, 4 s, 8 s ... capped at a minute
+pub fn backoff_delay(attempt: u32) -> Duration {
+    let factor: u64 = 1u64 << attempt.saturating_sub(1).min(16);
+    Duration::from_secs((BACKOFF_BASE_SECS * factor).min(BACKOFF_MAX_SECS))
+}
+
+// a download is written next to its reserved staging path as `<staging>.<ext>`; these are the
+// staging path and every file yt-dlp left with that prefix, finished or partial
+pub fn staged_download_paths(staging_path: &Path) -> Vec<PathBuf> {
+    let Some(file_name) = staging_path.file_name().and_then(|name| name.to_str()) else { return Vec::new() };
+    let Some(dir) = staging_path.parent() else { return Vec::new() };
+
+    let prefix: String = format!("{}.", file_name);
+    let mut paths: Vec<PathBuf> = vec![staging_path.to_path_buf()];
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        paths.extend(entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with(&prefix))));
+    }
+    paths
+}
+
+pub fn clean_staged_files(staging_path: &Path) {
+    for path in staged_download_paths(staging_path) {
+        if path.exists() {
+            if let Err(err) = std::fs::remove_file(&path) {
+                eprintln!("Error removing partial download {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+// registers a download so it can be cancelled and returns its id
+pub fn register_download() -> u32 {
+    let id: u32 = NEXT_DOWNLOAD_ID.fetch_add(1, Ordering::SeqCst);
+    ACTIVE_DOWNLOADS.lock().unwrap().insert(id, ActiveDownload::default());
+    id
+}
+
+pub fn unregister_download(id: u32) {
+    ACTIVE_DOWNLOADS.lock().unwrap().remove(&id);
+}
+
+pub fn is_cancelled(id: u32) -> bool {
+    ACTIVE_DOWNLOADS.lock().unwrap().get(&id).is_some_and(|download| download.cancelled)
+}
+
+fn take_child(id: u32) -> Option<CommandChild> {
+    ACTIVE_DOWNLOADS.lock().unwrap().get_mut(&id).and_then(|download| download.child.take())
+}
+
+fn kill_child(id: u32) {
+    if let Some(child) = take_child(id) {
+        let _ = child.kill();
+    }
+}
+
+// one run of yt-dlp, reporting progress lines through `on_progress`. Returns the other lines
+// yt-dlp printed to stdout
+async fn run_attempt(id: u32, args: &[String], options: &DownloadOptions, on_progress: &(impl Fn(YtDlpProgress) + Sync)) -> Result<Vec<String>, DownloadError> {
+    let mut yt_dlp_args: Vec<String> = vec![
+        "--newline".to_string(),
+        "--progress-template".to_string(), PROGRESS_TEMPLATE.to_string(),
+    ];
+    yt_dlp_args.extend_from_slice(args);
+
+    let (mut rx, child) = spawn_sidecar(yt_dlp_sidecar(), &yt_dlp_args).map_err(DownloadError::Fatal)?;
+    {
+        let mut downloads = ACTIVE_DOWNLOADS.lock().unwrap();
+        match downloads.get_mut(&id) {
+            Some(download) if !download.cancelled => download.child = Some(child),
+            _ => {
+                let _ = child.kill();
+                return Err(DownloadError::Cancelled);
+            }
+        }
+    }
+
+    let stall_timeout: Duration = Duration::from_secs(options.stall_timeout_secs.unwrap_or(120).max(1));
+    let deadline: Option<Instant> = options.timeout_secs.map(|secs| Instant::now() + Duration::from_secs(secs));
+    let mut stdout: Vec<String> = Vec::new();
+    let mut stderr: Vec<String> = Vec::new();
+    let exit_code: Option<i32> = loop {
+        let wait: Duration = match deadline {
+            Some(deadline) => stall_timeout.min(deadline.saturating_duration_since(Instant::now())),
+            None => stall_timeout,
+        };
+        let event = match tokio::time::timeout(wait, rx.recv()).await {
+            Ok(Some(event)) => event,
+            Ok(None) => break None,
+            Err(_) => {
+                kill_child(id);
+                let reason: &str = if deadline.is_some_and(|deadline| Instant::now() >= deadline) { "timed out" } else { "stalled" };
+                return Err(DownloadError::Transient(format!("Download {} after {} s without finishing", reason, wait.as_secs())));
+            }
+        };
+        match event {
+            CommandEvent::Stdout(line) => {
+                match parse_progress_line(&line) {
+                    Some(progress) => on_progress(progress),
+                    None => stdout.push(line),
+                }
+            }
+            CommandEvent::Stderr(line) => stderr.push(line),
+            CommandEvent::Error(err) => stderr.push(err),
+            CommandEvent::Terminated(payload) => break payload.code,
+            _ => {}
+        }
+    };
+    take_child(id);
+
+    if is_cancelled(id) {
+        return Err(DownloadError::Cancelled);
+    }
+    match exit_code {
+        Some(0) => Ok(stdout),
+        code => {
+            let stderr: String = stderr.join("\n");
+            let message: String = format!("yt-dlp exited with code {:?}: {}", code, stderr_tail(&stderr, 5));
+            if is_transient_error(&stderr) { Err(DownloadError::Transient(message)) } else { Err(DownloadError::Fatal(message)) }
+        }
+    }
+}
+
+// runs yt-dlp with `args` until it succeeds, retrying transient failures with backoff.
+// `on_progress` receives every progress update, including the retries. Returns what the
+// successful attempt printed to stdout besides progress
+pub async fn run_download(id: u32, url: &str, args: &[String], options: &DownloadOptions, on_progress: impl Fn(DownloadProgress) + Sync) -> Result<Vec<String>, DownloadError> {
+    let max_retries: u32 = options.max_retries.unwrap_or(3);
+    let mut attempt: u32 = 1;
+    loop {
+        let mut started = DownloadProgress::new(id, url, DownloadStatus::Downloading);
+        started.attempt = attempt;
+        on_progress(started);
+
+        let report = |progress: YtDlpProgress| on_progress(DownloadProgress {
+            attempt,
+            percent: progress.percent(),
+            downloaded_bytes: progress.downloaded_bytes,
+            total_bytes: progress.total_bytes,
+            speed: progress.speed,
+            eta_secs: progress.eta_secs,
+            ..DownloadProgress::new(id, url, DownloadStatus::Downloading)
+        });
+
+        match run_attempt(id, args, options, &report).await {
+            Ok(stdout) => return Ok(stdout),
+            Err(DownloadError::Transient(message)) if attempt <= max_retries => {
+                let mut retrying = DownloadProgress::new(id, url, DownloadStatus::Retrying);
+                retrying.attempt = attempt;
+                retrying.error = Some(message);
+                on_progress(retrying);
+
+                tokio::time::sleep(backoff_delay(attempt)).await;
+                if is_cancelled(id) {
+                    return Err(DownloadError::Cancelled);
+                }
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+// stops a running or queued download; its partial files are removed by the download itself
+#[tauri::command]
+pub fn cancel_download(id: u32) -> Result<(), String> {
+    let child: Option<CommandChild> = {
+        let mut downloads = ACTIVE_DOWNLOADS.lock().unwrap();
+        let download: &mut ActiveDownload = downloads.get_mut(&id).ok_or(format!("No active download {}", id))?;
+        download.cancelled = true;
+        download.child.take()
+    };
+    if let Some(child) = child {
+        child.kill().map_err(|e| format!("Error stopping download {}: {}", id, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_progress_line() {
+        let progress = parse_progress_line("chamber-progress 1048576 4194304 NA 524288.5 6").unwrap();
+        assert_eq!(progress, YtDlpProgress {
+            downloaded_bytes: Some(1048576),
+            total_bytes: Some(4194304),
+            speed: Some(524288.5),
+            eta_secs: Some(6),
+        });
+        assert_eq!(progress.percent(), Some(25.0));
+    }
+
+    #[test]
+    fn test_parse_progress_line_estimate_and_unknown() {
+        // fragmented downloads only know an estimate of the total
+        let progress = parse_progress_line("chamber-progress 2048 NA 4096.0 NA NA").unwrap();
+        assert_eq!(progress.total_bytes, Some(4096));
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.eta_secs, None);
+        assert_eq!(progress.percent(), Some(50.0));
+
+        let unknown = parse_progress_line("chamber-progress NA NA NA NA NA").unwrap();
+        assert_eq!(unknown.percent(), None);
+    }
+
+    #[test]
+    fn test_parse_progress_line_ignores_other_output() {
+        assert_eq!(parse_progress_line("[youtube] dQw4w9WgXcQ: Downloading webpage"), None);
+        assert_eq!(parse_progress_line("[download]  42.3% of 3.45MiB at 1.20MiB/s ETA 00:03"), None);
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        assert!(is_transient_error("ERROR: unable to download video data: HTTP Error 503: Service Unavailable"));
+        assert!(is_transient_error("ERROR: [youtube] abc: Read timed out."));
+        assert!(!is_transient_error("ERROR: [youtube] abc: Video unavailable"));
+        assert!(!is_transient_error("ERROR: [youtube] abc: Private video. Sign in if you've been granted access"));
+        assert!(!is_transient_error("ERROR: something unexpected"));
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        assert_eq!(backoff_delay(1), Duration::from_secs(2));
+        assert_eq!(backoff_delay(2), Duration::from_secs(4));
+        assert_eq!(backoff_delay(3), Duration::from_secs(8));
+        assert_eq!(backoff_delay(10), Duration::from_secs(60));
+        assert_eq!(backoff_delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_clean_staged_files() {
+        let dir = tempdir().unwrap();
+        let staging_path = dir.path().join("song.download");
+        for name in ["song.download", "song.download.webm.part", "song.download.webm.ytdl", "song.download.temp.mp3", "song.download.webm.part-Frag3", "song.download.mp3", "song.flac", "song-1.download"] {
+            File::create(dir.path().join(name)).unwrap();
+        }
+
+        clean_staged_files(&staging_path);
+        let mut remaining: Vec<String> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+            .collect();
+        remaining.sort();
+        assert_eq!(remaining, vec!["song-1.download", "song.flac"]);
+    }
+
+    #[test]
+    fn test_cancel_registered_download() {
+        let id = register_download();
+        assert!(!is_cancelled(id));
+        cancel_download(id).unwrap();
+        assert!(is_cancelled(id));
+        unregister_download(id);
+        assert!(cancel_download(id).is_err());
+    }
+}
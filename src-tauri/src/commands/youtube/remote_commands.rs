@@ -0,0 +1,93 @@
+use tokio::task;
+use tauri::Window;
+use crate::helper::files::create_audio_store_directory;
+use crate::models::remote_audio_model::{RemoteAudio, RemoteChapter};
+use crate::commands::youtube::downloader::{register_download, DownloadError, DownloadOptions, DownloadProgress, DownloadStatus};
+use crate::commands::youtube::yt_dlp_parser::{dump_info, dump_json};
+use crate::commands::youtube::youtube_commands::{
+    archive_key, download_unless_archived, emit_progress, report_panicked_download, split_download, track_download, DownloadResult,
+};
+
+// metadata of any url one of yt-dlp's extractors understands, e.g. SoundCloud, Bandcamp or Vimeo
+#[tauri::command]
+pub async fn fetch_remote_metadata(url: String) -> Result<RemoteAudio, String> {
+    dump_json(&url).await.map(|info| info.into_remote_audio(url))
+}
+
+// items from a search or a pasted YouTube url are known already; anything else is looked up
+// first, so the archive sees its extractor and id. A failed lookup leaves it to the download,
+// an album or playlist url fails the item
+async fn resolve_source(item: &RemoteAudio) -> Result<RemoteAudio, String> {
+    if archive_key(item).is_some() {
+        return Ok(item.clone());
+    }
+    match dump_info(&item.url).await {
+        Ok(info) => Ok(RemoteAudio {
+            url: item.url.clone(),
+            section_start: item.section_start,
+            section_end: item.section_end,
+            ..info.single_entry(&item.url)?.into_remote_audio(item.url.clone())
+        }),
+        Err(err) => {
+            eprintln!("Error fetching metadata of {}: {}", item.url, err);
+            Ok(item.clone())
+        }
+    }
+}
+
+// every item gets a download id right away and a "queued" progress event, so it can be cancelled
+// before yt-dlp starts. Items with `section_start` or `section_end` only fetch that part of the source.
+// With `split_chapters` every new download is also cut into one entry per chapter
+#[tauri::command(async)]
+pub async fn download_remote_audio(window: Window, audio_list: Vec<RemoteAudio>, force: Option<bool>, options: Option<DownloadOptions>) -> Result<Vec<DownloadResult>, String> {
+    let force: bool = force.unwrap_or(false);
+    let options: DownloadOptions = options.unwrap_or_default();
+    options.format.validate()?;
+    create_audio_store_directory()?;
+
+    let handles: Vec<(u32, RemoteAudio, task::JoinHandle<DownloadResult>)> = audio_list
+        .into_iter()
+        .map(|item| {
+            let download_id: u32 = register_download();
+            emit_progress(&window, DownloadProgress::new(download_id, &item.url, DownloadStatus::Queued));
+            let window = window.clone();
+            let options = options.clone();
+            let handle = task::spawn({
+                let item: RemoteAudio = item.clone();
+                async move {
+                    let resolved: Result<RemoteAudio, String> = resolve_source(&item).await;
+                    let (item, outcome) = match resolved {
+                        Ok(resolved) => {
+                            let outcome = track_download(&window, download_id, &resolved.url,
+                                download_unless_archived(&window, download_id, &resolved, force, &options)).await;
+                            (resolved, outcome)
+                        }
+                        Err(err) => {
+                            let outcome = track_download(&window, download_id, &item.url, async { Err(DownloadError::Fatal(err)) }).await;
+                            (item, outcome)
+                        }
+                    };
+                    let chapters: Vec<RemoteChapter> = item.chapters.clone();
+                    let mut result: DownloadResult = DownloadResult::from_outcome(download_id, item, outcome);
+                    if options.split_chapters {
+                        split_download(&mut result, chapters, options.chapter_playlist).await;
+                    }
+                    result
+                }
+            });
+            (download_id, item, handle)
+        })
+        .collect();
+
+    let mut results: Vec<DownloadResult> = Vec::with_capacity(handles.len());
+    for (download_id, item, handle) in handles {
+        results.push(match handle.await {
+            Ok(result) => result,
+            Err(err) => {
+                let message: String = report_panicked_download(&window, download_id, &item.url, err);
+                DownloadResult::from_outcome(download_id, item, Err(message))
+            }
+        });
+    }
+    Ok(results)
+}
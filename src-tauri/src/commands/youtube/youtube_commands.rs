@@ -1,12 +1,45 @@
-use rusty_ytdl::search::{SearchOptions, SearchResult, YouTube};
-use rusty_ytdl::search::SearchType::Video;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use rusty_ytdl::search::YouTube;
 use scraper::Html;
+use serde::{Serialize, Deserialize};
+use tokio::sync::Semaphore;
+use tokio::task;
+use tauri::Window;
 use crate::helper::constants::audio_store_path;
-use crate::helper::files::trim_invalid_file_characters;
-use crate::models::youtube_model::YouTubeAudio;
-use crate::helper::tools::meta_duration_to_minutes_raw;
+use crate::helper::files::{create_audio_store_directory, discard_output_path, next_available_path, trim_invalid_file_characters};
+use crate::models::audio_model::{Audio, NewAudio};
+use crate::models::playlist_model::{NewPlaylist, Playlist, PlaylistArg};
+use crate::models::remote_audio_model::{RemoteAudio, RemoteChapter};
+use crate::models::youtube_model::{YouTubeCollection, YouTubeSearchPage, YouTubeSearchParams, YouTubeUrlKind};
+use crate::helper::tools::{seconds_to_hh_mm_ss, seconds_to_minutes};
+use crate::helper::decoder::probe_duration;
 use crate::helper::db_lock::DB_LOCK;
+use crate::commands::artwork_commands::fetch_artwork_from_url;
+use crate::commands::processing::split::{source_chapters, split_entry_by_chapters, SplitResult};
+use crate::commands::audio_commands::{find_audio, find_audio_by_source, insert_audio_entry, spawn_import_analysis};
+use crate::commands::youtube::remote_commands::download_remote_audio;
+use crate::commands::playlist_audio_commands::insert_audio_into_playlist;
+use crate::commands::playlist_commands::insert_playlist_entry;
+use crate::commands::tag_commands::{audio_tag_fields, linked_cover_art};
+use crate::helper::tags::{write_tag_fields, TagFields};
+use crate::commands::download_archive_commands::{find_archive_entry, record_download};
+use crate::commands::youtube::downloader::{
+    clean_staged_files, register_download, run_download, unregister_download,
+    DownloadError, DownloadOptions, DownloadProgress, DownloadStatus,
+};
+use crate::commands::youtube::download_format::{section_args, validate_section};
+use crate::commands::youtube::yt_dlp_parser::{classify_url, dump_json, dump_playlist_page, extract_video_id, parse_printed_info, search_page, YtDlpInfo};
+use crate::models::download_archive_model::NewArchiveEntry;
 
+const YOUTUBE_EXTRACTOR: &str = "youtube";
+// extension of the placeholder a download is staged under until its real extension is known
+const STAGING_EXTENSION: &str = "download";
+const EXPAND_PAGE_SIZE: u32 = 50;
+// parallel yt-dlp processes while mirroring a playlist
+const MIRROR_CONCURRENCY: usize = 4;
 
 #[tauri::command]
 pub async fn youtube_suggestion(input: String) -> Result<Vec<String>, String> {
@@ -17,147 +50,431 @@ pub async fn youtube_suggestion(input: String) -> Result<Vec<String>, String> {
     Ok(suggestions)
 }
 
+// one page of search results; see `YouTubeSearchParams` for the defaults
 #[tauri::command(async)]
-pub async fn youtube_search(input: String) -> Result<Vec<YouTubeAudio>, String> {
-    let youtube = YouTube::new().map_err(|e| e.to_string())?;
+pub async fn youtube_search(input: String, params: Option<YouTubeSearchParams>) -> Result<YouTubeSearchPage, String> {
+    search_page(&input, &params.unwrap_or_default()).await
+}
+
+#[tauri::command]
+pub async fn youtube_search_by_url(url: String) -> Result<RemoteAudio, String> {
+    fetch_metadata(url).await
+}
 
-    let search_options = SearchOptions {
-        limit: 20,
-        search_type: Video,
-        safe_search: true,
+pub fn emit_progress(window: &Window, progress: DownloadProgress) {
+    let _ = window.emit("download-progress", progress);
+}
+
+// moves the file yt-dlp reported as its last line of output out of staging and stores it as
+// `<base_file_name>.<ext>` with the extension yt-dlp produced
+fn store_download(staging_path: &Path, base_file_name: &str, stdout: &[String]) -> Result<PathBuf, String> {
+    let downloaded: PathBuf = stdout.iter()
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .ok_or("yt-dlp did not report the downloaded file".to_string())?;
+    let extension: String = downloaded.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase())
+        .ok_or(format!("Downloaded file {} has no extension", downloaded.display()))?;
+    let store_dir: &Path = staging_path.parent().ok_or("Invalid staging path".to_string())?;
+
+    let output_path: PathBuf = next_available_path(store_dir, base_file_name, &extension)?;
+    fs::rename(&downloaded, &output_path)
+        .map_err(|e| discard_output_path(&output_path, format!("Unable to move {}: {}", downloaded.display(), e)))?;
+    Ok(output_path)
+}
+
+// downloads a single video into the audio store and adds it to the library. Progress is emitted
+// as "download-progress" events and partial files are removed when the download fails
+pub async fn download_entry(window: &Window, download_id: u32, yt_audio: &RemoteAudio, options: &DownloadOptions) -> Result<Audio, DownloadError> {
+    let yt_title = yt_audio.title.clone().unwrap_or_default();
+    let is_section: bool = yt_audio.section_start.is_some() || yt_audio.section_end.is_some();
+    // a section is named after its offsets so it does not read as the whole video
+    let base_file_name: String = if is_section {
+        let start: String = seconds_to_hh_mm_ss(yt_audio.section_start.unwrap_or(0.0) as u64);
+        let end: String = yt_audio.section_end.map(|end| seconds_to_hh_mm_ss(end.ceil() as u64)).unwrap_or("end".to_string());
+        trim_invalid_file_characters(&format!("{} {} {}", yt_title, start, end))
+    } else {
+        trim_invalid_file_characters(&yt_title)
     };
-    let res: Result<Vec<SearchResult>, rusty_ytdl::VideoError> = youtube.search(input, Some(&search_options)).await;
-    let structured_res: Vec<YouTubeAudio> = match res {
-        Ok(results) => results.into_iter().filter_map(|result| {
-            match result {
-                SearchResult::Video(video) => {
-                    let duration_minutes = meta_duration_to_minutes_raw(&video.duration_raw)?;
-                    if duration_minutes <= 420 {
-                        Some(YouTubeAudio {
-                            title: Some(video.title.clone()),
-                            thumbnail: video.thumbnails.first().map(|thumb| thumb.url.clone()),
-                            duration: Some(video.duration_raw.clone()),
-                            channel: Some(video.channel.name.clone()),
-                            views: Some(video.views.clone().to_string()),
-                            url: video.url.clone().to_string(),
-                        })
-                    } else {
-                        None
-                    }
-                },
-                _ => None,
-            }
-        }).collect(),
-        Err(e) => return Err(e.to_string()),
+    // yt-dlp writes `<staging>.<ext>`, the extension depends on the format options and the source
+    let staging_path: PathBuf = next_available_path(&audio_store_path(), &base_file_name, STAGING_EXTENSION)
+        .map_err(DownloadError::Fatal)?;
+
+    let mut args: Vec<String> = options.format.yt_dlp_args();
+    args.extend(section_args(yt_audio.section_start, yt_audio.section_end));
+    args.extend([
+        "--force-overwrites".to_string(),
+        // a url pointing into an album or playlist stands for the one item
+        "--no-playlist".to_string(),
+        "-o".to_string(), format!("{}.%(ext)s", staging_path.to_str().unwrap()),
+        // --print makes yt-dlp quiet, --progress keeps the progress lines. The metadata comes first,
+        // the path of the file is the last line
+        "--print".to_string(), "after_move:%()j".to_string(),
+        "--print".to_string(), "after_move:filepath".to_string(),
+        "--no-simulate".to_string(),
+        "--progress".to_string(),
+        "--postprocessor-args".to_string(), "ffmpeg:-strict -2".to_string(),
+        "--cookies".to_string(), "cookies.txt".to_string(),
+        yt_audio.url.clone(),
+    ]);
+    let stored = match run_download(download_id, &yt_audio.url, &args, options, |progress| emit_progress(window, progress)).await {
+        // every track of an album prints its metadata, and all of them were written to the same staging file
+        Ok(stdout) if stdout.iter().filter(|line| line.trim_start().starts_with('{')).count() > 1 => {
+            Err(DownloadError::Fatal(format!("{} is an album or playlist, add its entries one by one", yt_audio.url)))
+        }
+        Ok(stdout) => store_download(&staging_path, &base_file_name, &stdout)
+            .map(|output_path| (output_path, parse_printed_info(&stdout)))
+            .map_err(DownloadError::Fatal),
+        Err(err) => Err(err),
     };
-    Ok(structured_res)
-}           
+    clean_staged_files(&staging_path);
+    let (output_path, printed_info): (PathBuf, Result<YtDlpInfo, String>) = stored?;
 
-#[tauri::command]
-pub async fn youtube_search_by_url(url: String) -> Result<YouTubeAudio, String> {
-    fetch_metadata(url).await
+    // without the printed metadata the entry keeps the details the item came with
+    let download_result: RemoteAudio = match printed_info {
+        Ok(info) => info.into_remote_audio(yt_audio.url.clone()),
+        Err(err) => {
+            eprintln!("Error reading the metadata of {}: {}", yt_audio.url, err);
+            yt_audio.clone()
+        }
+    };
+
+    // the thumbnail becomes the artwork of the entry; a missing thumbnail is not an error
+    let thumbnail_url = yt_audio.thumbnail.clone().or(download_result.thumbnail.clone());
+    let artwork = match thumbnail_url {
+        Some(thumbnail_url) => fetch_artwork_from_url(&thumbnail_url).await
+            .map_err(|err| eprintln!("Error fetching thumbnail: {}", err))
+            .ok(),
+        None => None,
+    };
+
+    let (extractor, video_id): (Option<String>, Option<String>) = match (&download_result.extractor, &download_result.video_id) {
+        (Some(extractor), Some(video_id)) => (Some(extractor.clone()), Some(video_id.clone())),
+        _ => archive_key(yt_audio).unzip(),
+    };
+    // the stored file is measured, the length YouTube lists is only a fallback
+    let duration: String = match (probe_duration(output_path.to_str().unwrap()), yt_audio.section_end) {
+        (Ok(seconds), _) => seconds_to_minutes(seconds.round() as u64),
+        (Err(_), Some(end)) if is_section => seconds_to_minutes((end - yt_audio.section_start.unwrap_or(0.0)).round() as u64),
+        (Err(_), _) => download_result.duration.unwrap_or_default(),
+    };
+    let audio_type: String = output_path.extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_string();
+
+    // Lock multithreaded access to avoid database blocks
+    let db_lock = DB_LOCK.lock().await;
+    let created: Audio = insert_audio_entry(&NewAudio {
+        title: &download_result.title.unwrap_or_default(),
+        author: &download_result.channel.unwrap_or_default(),
+        path: output_path.to_str().unwrap(),
+        duration: &duration,
+        audio_type: &audio_type,
+        // the source url doubles as the comment, like in the tags of the file
+        comment: Some(&yt_audio.url),
+        artwork_hash: artwork.as_deref(),
+        source_video_id: video_id.as_deref(),
+        source_extractor: extractor.as_deref(),
+        source_url: Some(&yt_audio.url),
+        source_start: yt_audio.section_start,
+        source_end: yt_audio.section_end,
+        ..Default::default()
+    }).map_err(|e| DownloadError::Fatal(discard_output_path(&output_path, e)))?;
+    drop(db_lock);
+
+    // the file gets the details of the row and the thumbnail as cover; formats without tag support
+    // are kept untagged
+    let fields: TagFields = TagFields {
+        recording_date: download_result.upload_date.clone(),
+        ..audio_tag_fields(&created)
+    };
+    let tagged: Result<(), String> = linked_cover_art(&created)
+        .and_then(|cover_art| write_tag_fields(&created.path, &fields, cover_art.as_deref()));
+    if let Err(err) = tagged {
+        eprintln!("Error embedding metadata into {}: {}", created.path, err);
+    }
+
+    spawn_import_analysis(created.id, created.path.clone());
+    Ok(created)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DownloadResult {
+    // id used by the progress events and `cancel_download`
+    pub download_id: u32,
+    pub url: String,
+    pub video_id: Option<String>,
+    pub audio_id: Option<i32>,
+    // the video was downloaded before and was not fetched again
+    pub skipped: bool,
+    // entries created from the chapters when `split_chapters` is set
+    pub chapters: Option<SplitResult>,
+    pub error: Option<String>,
+}
+
+impl DownloadResult {
+    pub fn from_outcome(download_id: u32, yt_audio: RemoteAudio, outcome: Result<(Option<i32>, bool), String>) -> Self {
+        match outcome {
+            Ok((audio_id, skipped)) => DownloadResult {
+                download_id,
+                url: yt_audio.url,
+                video_id: yt_audio.video_id,
+                audio_id,
+                skipped,
+                chapters: None,
+                error: None,
+            },
+            Err(err) => DownloadResult {
+                download_id,
+                url: yt_audio.url,
+                video_id: yt_audio.video_id,
+                audio_id: None,
+                skipped: false,
+                chapters: None,
+                error: Some(err),
+            },
+        }
+    }
+}
+
+// the extractor and id the download archive knows an item by. Items without an extractor are
+// YouTube videos when their url says so
+pub fn archive_key(item: &RemoteAudio) -> Option<(String, String)> {
+    match (&item.extractor, &item.video_id) {
+        (Some(extractor), Some(video_id)) => Some((extractor.clone(), video_id.clone())),
+        _ => extract_video_id(&item.url).map(|video_id| (YOUTUBE_EXTRACTOR.to_string(), video_id)),
+    }
+}
+
+// drops repeated videos, a playlist may list the same video more than once. The first occurrence is kept
+pub fn dedupe_entries(entries: Vec<RemoteAudio>) -> Vec<RemoteAudio> {
+    let mut seen: HashSet<(String, String)> = HashSet::new();
+    entries.into_iter()
+        .filter(|entry| match archive_key(entry) {
+            Some(key) => seen.insert(key),
+            None => true,
+        })
+        .collect()
+}
+
+// downloads the entry unless the download archive lists its video; `force` fetches it again.
+// Returns the entry the video was stored as, if it still exists, and whether the download was skipped.
+// Sections of a video are always downloaded and are not archived
+pub async fn download_unless_archived(window: &Window, download_id: u32, yt_audio: &RemoteAudio, force: bool, options: &DownloadOptions) -> Result<(Option<i32>, bool), DownloadError> {
+    let is_section: bool = yt_audio.section_start.is_some() || yt_audio.section_end.is_some();
+    if is_section {
+        validate_section(yt_audio.section_start, yt_audio.section_end, yt_audio.duration_seconds).map_err(DownloadError::Fatal)?;
+        let created: Audio = download_entry(window, download_id, yt_audio, options).await?;
+        return Ok((Some(created.id), false));
+    }
+
+    if let (false, Some((extractor, video_id))) = (force, archive_key(yt_audio)) {
+        if let Some(archived) = find_archive_entry(&extractor, &video_id).map_err(DownloadError::Fatal)? {
+            // the archive keeps its records when entries are deleted
+            let audio_id: Option<i32> = archived.audio_id.filter(|audio_id| find_audio(*audio_id).is_ok());
+            return Ok((audio_id, true));
+        }
+    }
+
+    let created: Audio = download_entry(window, download_id, yt_audio, options).await?;
+    if let (Some(extractor), Some(video_id)) = (created.source_extractor.as_deref(), created.source_video_id.as_deref()) {
+        record_download(&NewArchiveEntry {
+            extractor,
+            video_id,
+            title: Some(&created.title),
+            audio_id: Some(created.id),
+        }).map_err(DownloadError::Fatal)?;
+    }
+    Ok((Some(created.id), false))
+}
+
+// runs `download` for a registered download and reports how it ended as a progress event
+pub async fn track_download<F>(window: &Window, download_id: u32, url: &str, download: F) -> Result<(Option<i32>, bool), String>
+where
+    F: std::future::Future<Output = Result<(Option<i32>, bool), DownloadError>>,
+{
+    let outcome = download.await;
+    unregister_download(download_id);
+
+    let mut finished = DownloadProgress::new(download_id, url, DownloadStatus::Finished);
+    match &outcome {
+        Ok(_) => finished.percent = Some(100.0),
+        Err(DownloadError::Cancelled) => finished.status = DownloadStatus::Cancelled,
+        Err(err) => {
+            finished.status = DownloadStatus::Failed;
+            finished.error = Some(err.message());
+        }
+    }
+    emit_progress(window, finished);
+    outcome.map_err(|err| err.message())
+}
+
+// a download task that panicked fails only its own item; it is unregistered and reported like any
+// other failure, and the message is returned for its result
+pub fn report_panicked_download(window: &Window, download_id: u32, url: &str, err: task::JoinError) -> String {
+    unregister_download(download_id);
+    let message: String = format!("Download stopped unexpectedly: {}", err);
+    let mut failed = DownloadProgress::new(download_id, url, DownloadStatus::Failed);
+    failed.error = Some(message.clone());
+    emit_progress(window, failed);
+    message
+}
+
+// splits a new download into its chapters. Videos without chapters are left whole; a failed split
+// is reported on the result, the download itself is kept
+pub async fn split_download(result: &mut DownloadResult, chapters: Vec<RemoteChapter>, into_playlist: bool) {
+    let Some(audio_id) = result.audio_id.filter(|_| !result.skipped) else { return };
+    let split: Result<Option<SplitResult>, String> = async {
+        let source: Audio = find_audio(audio_id)?;
+        let chapters: Vec<RemoteChapter> = if chapters.is_empty() { source_chapters(&source).await? } else { chapters };
+        if chapters.is_empty() {
+            return Ok(None);
+        }
+        let playlist: Option<PlaylistArg> = into_playlist.then_some(PlaylistArg { title: None, created_on: None });
+        split_entry_by_chapters(&source, &chapters, playlist).await.map(Some)
+    }.await;
+    match split {
+        Ok(chapters) => result.chapters = chapters,
+        Err(err) => result.error = Some(format!("Downloaded, but splitting into chapters failed: {}", err)),
+    }
 }
 
+// the YouTube search page downloads through the same pipeline as any other source
 #[tauri::command(async)]
-pub async fn download_audio(audio_list: Vec<YouTubeAudio>) -> Result<(), String> {
-    pub use crate::helper::files:: create_audio_store_directory;
-    use crate::models::audio_model::NewAudio;
-    use crate::db::establish_connection;
-    use diesel::SqliteConnection;
-    use diesel::prelude::*;
-    use crate::schema::audio::dsl::*;
-    use tokio::sync::mpsc;
-    use tokio::task;
-    use tauri::api::process::Command;
+pub async fn download_audio(window: Window, audio_list: Vec<RemoteAudio>, force: Option<bool>, options: Option<DownloadOptions>) -> Result<Vec<DownloadResult>, String> {
+    download_remote_audio(window, audio_list, force, options).await
+}
 
-    create_audio_store_directory()?;
+// lists the entries behind a playlist, channel or mix url one page at a time; `page` starts at 1.
+// A plain video url yields a single entry
+#[tauri::command(async)]
+pub async fn youtube_expand_url(url: String, page: Option<u32>, page_size: Option<u32>) -> Result<YouTubeCollection, String> {
+    let kind: YouTubeUrlKind = classify_url(&url)?;
+    let page: u32 = page.unwrap_or(1).max(1);
+    let page_size: u32 = page_size.unwrap_or(EXPAND_PAGE_SIZE).clamp(1, 500);
 
-    let (tx, mut rx) = mpsc::channel(32);
-    let mut handles = vec![];
-    for yt_audio in audio_list {
-        let tx = tx.clone();
-        let db_lock = DB_LOCK.clone();
-        // Spawn a task for each audio download
-        let handle = task::spawn(async move {
-            let audio_store_path = audio_store_path();
-            let yt_title = yt_audio.title.clone().unwrap_or_default();
-            let mut output_path = audio_store_path.join(format!("{}.mp3", trim_invalid_file_characters(&yt_title)));
-            let mut counter = 0;
-
-            while output_path.exists() {
-                counter += 1;
-                let dup_title = format!("{}-{}", trim_invalid_file_characters(&yt_title), counter);
-                output_path = audio_store_path.join(format!("{}.mp3", dup_title.replace(" ", "_")));
-            }
+    if kind == YouTubeUrlKind::Video {
+        let entry: RemoteAudio = fetch_metadata(url).await?;
+        return Ok(YouTubeCollection {
+            kind,
+            id: entry.video_id.clone(),
+            title: entry.title.clone(),
+            uploader: entry.channel.clone(),
+            total: Some(1),
+            page: 1,
+            page_size,
+            has_more: false,
+            entries: vec![entry],
+        });
+    }
+    dump_playlist_page(&url, kind, page, page_size).await
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MirrorResult {
+    pub playlist_id: i32,
+    pub entries: Vec<DownloadResult>,
+}
 
-            let args = vec![
-                "-x",
-                "--max-filesize", "500m",
-                "-o", output_path.to_str().unwrap(),
-                "--postprocessor-args", "ffmpeg:-strict -2",
-                "--cookies", "cookies.txt",
-                &yt_audio.url,
-            ];
-
-            let command = if cfg!(target_os = "windows") { "yt-dlp.exe" } else { "yt-dlp" };
-            Command::new_sidecar(command)
-                .expect("failed to create `my-sidecar` binary command")
-                .args(&args)
-                .spawn()
-                .expect("Failed to spawn sidecar");
-
-            let timeout_duration = std::time::Duration::from_secs(120);
-            let start_time = std::time::Instant::now();
-            // Wait for the output path to exist
-            while !std::path::Path::new(output_path.to_str().unwrap()).exists() {
-                if start_time.elapsed() > timeout_duration {
-                    panic!("Timeout waiting for output file to exist");
+// mirrors a playlist, channel or mix into a new playlist in the same order. Videos already in the
+// library are reused instead of downloaded again and videos listed twice are added once; entries
+// that fail, and archived videos whose entry was deleted, are left out of the playlist
+#[tauri::command(async)]
+pub async fn download_youtube_playlist(window: Window, url: String, playlist: Option<PlaylistArg>, options: Option<DownloadOptions>) -> Result<MirrorResult, String> {
+    let kind: YouTubeUrlKind = classify_url(&url)?;
+    if kind == YouTubeUrlKind::Video {
+        return Err("The url does not point to a playlist, channel or mix".to_string());
+    }
+    let options: DownloadOptions = options.unwrap_or_default();
+    options.format.validate()?;
+
+    let mut page: u32 = 1;
+    let first_page: YouTubeCollection = dump_playlist_page(&url, kind, page, EXPAND_PAGE_SIZE).await?;
+    let playlist_arg: PlaylistArg = playlist.unwrap_or(PlaylistArg { title: None, created_on: None });
+    let title: String = playlist_arg.title
+        .or(first_page.title.clone())
+        .unwrap_or("YouTube playlist".to_string());
+    let mut has_more: bool = first_page.has_more;
+    let mut entries: Vec<RemoteAudio> = first_page.entries;
+    while has_more {
+        page += 1;
+        let next_page: YouTubeCollection = dump_playlist_page(&url, kind, page, EXPAND_PAGE_SIZE).await?;
+        has_more = next_page.has_more && !next_page.entries.is_empty();
+        entries.extend(next_page.entries);
+    }
+    let entries: Vec<RemoteAudio> = dedupe_entries(entries);
+
+    create_audio_store_directory()?;
+    let mirror_playlist: Playlist = insert_playlist_entry(&NewPlaylist {
+        title: &title,
+        created_on: &playlist_arg.created_on.unwrap_or_default(),
+    })?;
+
+    let download_slots: Arc<Semaphore> = Arc::new(Semaphore::new(MIRROR_CONCURRENCY));
+    let handles: Vec<(u32, RemoteAudio, task::JoinHandle<Result<(Option<i32>, bool), String>>)> = entries
+        .into_iter()
+        .map(|entry| {
+            let download_id: u32 = register_download();
+            emit_progress(&window, DownloadProgress::new(download_id, &entry.url, DownloadStatus::Queued));
+            let download_slots = download_slots.clone();
+            let window = window.clone();
+            let options = options.clone();
+            let handle = task::spawn({
+                let entry: RemoteAudio = entry.clone();
+                async move {
+                    let _slot = download_slots.acquire_owned().await;
+                    track_download(&window, download_id, &entry.url, async {
+                        let existing = match archive_key(&entry) {
+                            Some((extractor, video_id)) => find_audio_by_source(&extractor, &video_id),
+                            None => Ok(None),
+                        };
+                        match existing {
+                            Ok(Some(existing)) => Ok((Some(existing.id), true)),
+                            Ok(None) => download_unless_archived(&window, download_id, &entry, false, &options).await,
+                            Err(err) => Err(DownloadError::Fatal(err)),
+                        }
+                    }).await
                 }
-                std::thread::sleep(std::time::Duration::from_secs(1));
-            }
-            
-            // Lock multithreaded access to avoid database blocks
-            let download_result = fetch_metadata(yt_audio.url).await.unwrap();
-            let _lock = db_lock.lock().await;
-        
-            let mut connection: SqliteConnection = establish_connection();
-            let new_audio: NewAudio<'_> = NewAudio{
-                title: &download_result.title.unwrap_or_default(),
-                author: &download_result.channel.unwrap_or_default(),
-                path: output_path.to_str().unwrap(),
-                duration: &download_result.duration.unwrap_or_default(),
-                audio_type: "mp3",
-            };
-
-            let result: Result<usize, diesel::result::Error> = diesel::insert_into(audio)
-                .values(&new_audio)
-                .execute(&mut connection);
-
-            if result.is_err() {
-                let _ = tx.send(Err(format!("Error: Could not add audio entry to database: {:?}", result.err()))).await;
-            } else {
-                let _ = tx.send(Ok(())).await;
+            });
+            (download_id, entry, handle)
+        })
+        .collect();
+
+    // entries are added after all downloads are awaited in order so the playlist keeps the source order,
+    // each entry once
+    let mut added: HashSet<i32> = HashSet::new();
+    let mut results: Vec<DownloadResult> = Vec::with_capacity(handles.len());
+    for (download_id, entry, handle) in handles {
+        let outcome = handle.await.unwrap_or_else(|e| Err(report_panicked_download(&window, download_id, &entry.url, e)));
+        let outcome = outcome.and_then(|(entry_audio_id, skipped)| match entry_audio_id {
+            Some(entry_audio_id) if added.insert(entry_audio_id) => {
+                insert_audio_into_playlist(mirror_playlist.id, entry_audio_id).map(|_| (Some(entry_audio_id), skipped))
             }
+            _ => Ok((entry_audio_id, skipped)),
         });
-
-        handles.push(handle);
+        results.push(DownloadResult::from_outcome(download_id, entry, outcome));
     }
 
-    drop(tx);
+    Ok(MirrorResult { playlist_id: mirror_playlist.id, entries: results })
+}
 
-    while let Some(result) = rx.recv().await {
-        if let Err(e) = result {
-            return Err(e);
+// metadata comes from yt-dlp; for YouTube videos the page scraper fills in when yt-dlp fails
+pub async fn fetch_metadata(url: String) -> Result<RemoteAudio, String> {
+    match dump_json(&url).await {
+        Ok(info) => Ok(info.into_remote_audio(url)),
+        Err(err) if extract_video_id(&url).is_some() => {
+            eprintln!("yt-dlp metadata failed, falling back to the page: {}", err);
+            scrape_metadata(url).await
         }
+        Err(err) => Err(err),
     }
-
-    for handle in handles {
-        let _ = handle.await;
-    }
-    
-    Ok(())
 }
-pub async fn fetch_metadata(url: String) -> Result<YouTubeAudio, String> {
+
+async fn scrape_metadata(url: String) -> Result<RemoteAudio, String> {
     use crate::commands::youtube::yt_web_parser::{
         extract_channel, 
         extract_views,
@@ -177,14 +494,50 @@ pub async fn fetch_metadata(url: String) -> Result<YouTubeAudio, String> {
         extract_views(&document).map_err(|e| e.to_string())?,
     );
     
-    let youtube_audio: YouTubeAudio = YouTubeAudio{
+    let youtube_audio: RemoteAudio = RemoteAudio{
+        extractor: Some(YOUTUBE_EXTRACTOR.to_string()),
+        video_id: extract_video_id(&url),
         title: Some(title),
         thumbnail: Some(thumbnail),
         duration: Some(duration),
         channel: Some(channel),
         views: Some(views),
         url,
+        ..Default::default()
     };
 
     Ok(youtube_audio)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, extractor: Option<&str>, video_id: Option<&str>) -> RemoteAudio {
+        RemoteAudio {
+            url: url.to_string(),
+            extractor: extractor.map(|s| s.to_string()),
+            video_id: video_id.map(|s| s.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_dedupe_entries() {
+        let entries = vec![
+            entry("https://www.youtube.com/watch?v=aaaaaaaaaaa", None, None),
+            entry("https://youtu.be/bbbbbbbbbbb", Some("youtube"), Some("bbbbbbbbbbb")),
+            entry("https://www.youtube.com/watch?v=aaaaaaaaaaa&list=PL1", Some("youtube"), Some("aaaaaaaaaaa")),
+            entry("https://example.com/a", None, None),
+            entry("https://example.com/a", None, None),
+        ];
+        let urls: Vec<String> = dedupe_entries(entries).into_iter().map(|entry| entry.url).collect();
+        // entries without a key are kept, the archive cannot tell them apart
+        assert_eq!(urls, vec![
+            "https://www.youtube.com/watch?v=aaaaaaaaaaa",
+            "https://youtu.be/bbbbbbbbbbb",
+            "https://example.com/a",
+            "https://example.com/a",
+        ]);
+    }
+}
@@ -0,0 +1,582 @@
+use reqwest::Url;
+use serde::Deserialize;
+use crate::helper::sidecar::{run_sidecar, yt_dlp_sidecar};
+use crate::helper::tools::seconds_to_minutes;
+use crate::models::remote_audio_model::{RemoteAudio, RemoteChapter, RemoteThumbnail};
+use crate::models::youtube_model::{
+  SearchDuration, SearchSort, SearchUploadDate, YouTubeCollection, YouTubeSearchItem, YouTubeSearchPage,
+  YouTubeSearchParams, YouTubeUrlKind,
+};
+
+pub const DEFAULT_SEARCH_LIMIT: u32 = 20;
+pub const MAX_SEARCH_LIMIT: u32 = 100;
+// the PREF cookie YouTube uses to switch on restricted mode
+const SAFE_SEARCH_COOKIE: &str = "Cookie:PREF=f2=8000000";
+
+// the subset of the `--dump-json` output we use, every field may be missing depending on the extractor
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct YtDlpInfo {
+  // "playlist" for albums, sets and other urls that stand for several entries
+  #[serde(rename = "_type")]
+  pub kind: Option<String>,
+  pub id: Option<String>,
+  // e.g. "youtube", "soundcloud" or "BandcampAlbum"
+  pub extractor: Option<String>,
+  // extractor of flat playlist entries
+  pub ie_key: Option<String>,
+  // entry url of flat playlist listings
+  pub url: Option<String>,
+  pub title: Option<String>,
+  pub uploader: Option<String>,
+  pub channel: Option<String>,
+  pub artist: Option<String>,
+  pub creator: Option<String>,
+  pub duration: Option<f64>,
+  pub view_count: Option<u64>,
+  // YYYYMMDD
+  pub upload_date: Option<String>,
+  pub description: Option<String>,
+  pub license: Option<String>,
+  pub webpage_url: Option<String>,
+  pub thumbnail: Option<String>,
+  pub thumbnails: Option<Vec<YtDlpThumbnail>>,
+  pub tags: Option<Vec<String>>,
+  pub chapters: Option<Vec<YtDlpChapter>>,
+  // flat entries of a playlist
+  pub entries: Option<Vec<YtDlpInfo>>,
+}
+
+// `--flat-playlist --dump-single-json` output of a playlist, channel or mix
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct YtDlpPlaylist {
+  pub id: Option<String>,
+  pub title: Option<String>,
+  pub uploader: Option<String>,
+  pub channel: Option<String>,
+  pub playlist_count: Option<u64>,
+  pub entries: Vec<YtDlpInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YtDlpThumbnail {
+  pub url: String,
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct YtDlpChapter {
+  pub title: Option<String>,
+  pub start_time: f64,
+  pub end_time: f64,
+}
+
+pub fn parse_info_json(json: &str) -> Result<YtDlpInfo, String> {
+  serde_json::from_str(json).map_err(|e| format!("Invalid yt-dlp metadata: {}", e))
+}
+
+// the info JSON a download prints with `--print after_move:%()j`, among the other lines it printed
+pub fn parse_printed_info(lines: &[String]) -> Result<YtDlpInfo, String> {
+  let json: &String = lines.iter()
+    .find(|line| line.trim_start().starts_with('{'))
+    .ok_or("yt-dlp did not print the metadata".to_string())?;
+  parse_info_json(json)
+}
+
+pub fn parse_playlist_json(json: &str) -> Result<YtDlpPlaylist, String> {
+  serde_json::from_str(json).map_err(|e| format!("Invalid yt-dlp playlist: {}", e))
+}
+
+pub fn classify_url(url: &str) -> Result<YouTubeUrlKind, String> {
+  let parsed: Url = Url::parse(url).map_err(|e| format!("Invalid url {}: {}", url, e))?;
+  if let Some((_, list)) = parsed.query_pairs().find(|(key, _)| key == "list") {
+    // mixes are generated lists whose ids start with RD
+    return Ok(if list.starts_with("RD") { YouTubeUrlKind::Mix } else { YouTubeUrlKind::Playlist });
+  }
+
+  let segments: Vec<&str> = parsed.path_segments().map(|segments| segments.filter(|s| !s.is_empty()).collect()).unwrap_or_default();
+  match segments.first() {
+    Some(first) if first.starts_with('@') => Ok(YouTubeUrlKind::Channel),
+    Some(&"channel") | Some(&"c") | Some(&"user") => Ok(YouTubeUrlKind::Channel),
+    _ => Ok(YouTubeUrlKind::Video),
+  }
+}
+
+// video id of watch, short, embed and youtu.be urls
+pub fn extract_video_id(url: &str) -> Option<String> {
+  let parsed: Url = Url::parse(url).ok()?;
+  let host: &str = parsed.host_str()?;
+  let segments: Vec<&str> = parsed.path_segments()?.filter(|s| !s.is_empty()).collect();
+
+  let video_id: Option<String> = if host.ends_with("youtu.be") {
+    segments.first().map(|s| s.to_string())
+  } else if host.ends_with("youtube.com") {
+    match segments.as_slice() {
+      ["watch"] => parsed.query_pairs().find(|(key, _)| key == "v").map(|(_, value)| value.to_string()),
+      ["shorts", id] | ["embed", id] | ["live", id] => Some(id.to_string()),
+      _ => None,
+    }
+  } else {
+    None
+  };
+  video_id.filter(|id| !id.is_empty())
+}
+
+// the root of a channel lists its tabs rather than videos, so point yt-dlp at the uploads
+pub fn collection_url(url: &str, kind: YouTubeUrlKind) -> String {
+  if kind != YouTubeUrlKind::Channel {
+    return url.to_string();
+  }
+  let Ok(mut parsed) = Url::parse(url) else { return url.to_string() };
+  let segments: Vec<String> = parsed.path_segments()
+    .map(|segments| segments.filter(|s| !s.is_empty()).map(|s| s.to_string()).collect())
+    .unwrap_or_default();
+  let has_tab: bool = if segments.first().is_some_and(|first| first.starts_with('@')) { segments.len() > 1 } else { segments.len() > 2 };
+  if !has_tab {
+    parsed.set_path(&format!("{}/videos", segments.join("/")));
+  }
+  parsed.to_string()
+}
+
+// 20240131 -> 2024-01-31
+fn format_upload_date(date: &str) -> Option<String> {
+  if date.len() != 8 || !date.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+  Some(format!("{}-{}-{}", &date[0..4], &date[4..6], &date[6..8]))
+}
+
+impl YtDlpInfo {
+  // `--no-playlist` does not apply to Bandcamp albums or SoundCloud sets, those urls list their tracks instead
+  pub fn single_entry(self, url: &str) -> Result<Self, String> {
+    if self.kind.as_deref() != Some("playlist") {
+      return Ok(self);
+    }
+    let count: usize = self.entries.as_ref().map(|entries| entries.len()).unwrap_or(0);
+    Err(format!("{} is an album or playlist of {} entries, add its entries one by one", url, count))
+  }
+
+  // flat listings link their entries by url or, for videos, only by id
+  fn entry_url(&self) -> Option<String> {
+    self.url.clone()
+      .or(self.webpage_url.clone())
+      .or(self.id.as_ref().map(|video_id| format!("https://www.youtube.com/watch?v={}", video_id)))
+  }
+
+  pub fn into_remote_audio(self, url: String) -> RemoteAudio {
+    let duration_seconds: Option<u64> = self.duration.map(|seconds| seconds.round() as u64);
+    let chapters: Vec<RemoteChapter> = self.chapters.unwrap_or_default()
+      .into_iter()
+      .enumerate()
+      .map(|(index, chapter)| RemoteChapter {
+        title: chapter.title.filter(|title| !title.is_empty()).unwrap_or(format!("Chapter {}", index + 1)),
+        start_time: chapter.start_time,
+        end_time: chapter.end_time,
+      })
+      .collect();
+    let thumbnails: Vec<RemoteThumbnail> = self.thumbnails.unwrap_or_default()
+      .into_iter()
+      .map(|thumbnail| RemoteThumbnail { url: thumbnail.url, width: thumbnail.width, height: thumbnail.height })
+      .collect();
+
+    RemoteAudio {
+      extractor: self.extractor.or(self.ie_key).map(|extractor| extractor.to_lowercase()),
+      video_id: self.id,
+      title: self.title,
+      channel: self.uploader.or(self.channel).or(self.artist).or(self.creator),
+      views: self.view_count.map(|views| views.to_string()),
+      duration: duration_seconds.map(seconds_to_minutes),
+      // yt-dlp lists thumbnails from worst to best
+      thumbnail: self.thumbnail.or(thumbnails.last().map(|thumbnail| thumbnail.url.clone())),
+      url,
+      duration_seconds,
+      upload_date: self.upload_date.as_deref().and_then(format_upload_date),
+      description: self.description,
+      license: self.license,
+      webpage_url: self.webpage_url,
+      tags: self.tags.unwrap_or_default(),
+      chapters,
+      thumbnails,
+      ..Default::default()
+    }
+  }
+}
+
+impl YtDlpPlaylist {
+  // whether pages after `page` have entries, judged from the unfiltered entries of this page
+  fn has_more(&self, page: u32, page_size: u32) -> bool {
+    match self.playlist_count {
+      Some(total) => (page as u64) * (page_size as u64) < total,
+      None => self.entries.len() as u32 >= page_size,
+    }
+  }
+
+  // entries of one page; `page` starts at 1
+  pub fn into_collection(self, kind: YouTubeUrlKind, page: u32, page_size: u32) -> YouTubeCollection {
+    let has_more: bool = self.has_more(page, page_size);
+    let entries: Vec<RemoteAudio> = self.entries
+      .into_iter()
+      .filter_map(|entry| {
+        let url: String = entry.entry_url()?;
+        Some(entry.into_remote_audio(url))
+      })
+      .collect();
+
+    YouTubeCollection {
+      kind,
+      id: self.id,
+      title: self.title,
+      uploader: self.uploader.or(self.channel),
+      total: self.playlist_count,
+      page,
+      page_size,
+      has_more,
+      entries,
+    }
+  }
+
+  // results of one search page. Videos are always kept, playlists, mixes and channels only when
+  // the parameters ask for them
+  pub fn into_search_page(self, params: &YouTubeSearchParams, page: u32, page_size: u32) -> YouTubeSearchPage {
+    let next_page_token: Option<String> = self.has_more(page, page_size).then(|| (page + 1).to_string());
+    let results: Vec<YouTubeSearchItem> = self.entries
+      .into_iter()
+      .filter_map(|entry| {
+        let url: String = entry.entry_url()?;
+        let kind: YouTubeUrlKind = classify_url(&url).ok()?;
+        let wanted: bool = match kind {
+          YouTubeUrlKind::Video => true,
+          YouTubeUrlKind::Playlist | YouTubeUrlKind::Mix => params.include_playlists,
+          YouTubeUrlKind::Channel => params.include_channels,
+        };
+        wanted.then(|| YouTubeSearchItem { kind, entry: entry.into_remote_audio(url) })
+      })
+      .collect();
+
+    YouTubeSearchPage { results, next_page_token }
+  }
+}
+
+// the `sp` parameter of YouTube's results page, a base64 encoded protobuf message holding the sort
+// order (field 1) and the filters (field 2: upload date 1, type 2, duration 3)
+pub fn search_filter_param(params: &YouTubeSearchParams) -> Option<String> {
+  let mut filters: Vec<u8> = Vec::new();
+  if let Some(upload_date) = params.upload_date {
+    let value: u8 = match upload_date {
+      SearchUploadDate::LastHour => 1,
+      SearchUploadDate::Today => 2,
+      SearchUploadDate::ThisWeek => 3,
+      SearchUploadDate::ThisMonth => 4,
+      SearchUploadDate::ThisYear => 5,
+    };
+    filters.extend([0x08, value]);
+  }
+  // restricting the type to videos only works when nothing else is wanted
+  if !params.include_playlists && !params.include_channels {
+    filters.extend([0x10, 1]);
+  }
+  if let Some(duration) = params.duration {
+    let value: u8 = match duration {
+      SearchDuration::Short => 1,
+      SearchDuration::Long => 2,
+      SearchDuration::Medium => 3,
+    };
+    filters.extend([0x18, value]);
+  }
+
+  let mut message: Vec<u8> = Vec::new();
+  match params.sort {
+    Some(SearchSort::Date) => message.extend([0x08, 2]),
+    Some(SearchSort::Views) => message.extend([0x08, 3]),
+    Some(SearchSort::Relevance) | None => {}
+  }
+  if !filters.is_empty() {
+    message.extend([0x12, filters.len() as u8]);
+    message.extend(filters);
+  }
+  (!message.is_empty()).then(|| base64::encode(message))
+}
+
+pub fn search_url(query: &str, params: &YouTubeSearchParams) -> Result<String, String> {
+  let mut url: Url = Url::parse_with_params("https://www.youtube.com/results", &[("search_query", query)])
+    .map_err(|e| format!("Invalid search: {}", e))?;
+  if let Some(filter) = search_filter_param(params) {
+    url.query_pairs_mut().append_pair("sp", &filter);
+  }
+  Ok(url.to_string())
+}
+
+// page tokens are page numbers starting at 1; no token is the first page
+pub fn parse_page_token(token: Option<&str>) -> Result<u32, String> {
+  match token {
+    None => Ok(1),
+    Some(token) => token.parse::<u32>().ok().filter(|page| *page >= 1).ok_or(format!("Invalid page token {}", token)),
+  }
+}
+
+// one page of a listing yt-dlp can flatten: playlists, channels and search results
+async fn dump_flat_page(url: &str, page: u32, page_size: u32, extra_args: &[String]) -> Result<YtDlpPlaylist, String> {
+  let first: u32 = (page - 1) * page_size + 1;
+  let mut args: Vec<String> = vec![
+    "--flat-playlist".to_string(),
+    "--dump-single-json".to_string(),
+    "--yes-playlist".to_string(),
+    "--no-warnings".to_string(),
+    "--playlist-items".to_string(), format!("{}:{}", first, first + page_size - 1),
+  ];
+  args.extend_from_slice(extra_args);
+  args.push(url.to_string());
+  let output = run_sidecar(yt_dlp_sidecar(), &args).await?;
+  parse_playlist_json(&output.stdout)
+}
+
+pub async fn dump_playlist_page(url: &str, kind: YouTubeUrlKind, page: u32, page_size: u32) -> Result<YouTubeCollection, String> {
+  Ok(dump_flat_page(&collection_url(url, kind), page, page_size, &[]).await?.into_collection(kind, page, page_size))
+}
+
+pub async fn search_page(query: &str, params: &YouTubeSearchParams) -> Result<YouTubeSearchPage, String> {
+  let page: u32 = parse_page_token(params.page_token.as_deref())?;
+  let limit: u32 = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+  let extra_args: Vec<String> = if params.safe_search.unwrap_or(true) {
+    vec!["--add-headers".to_string(), SAFE_SEARCH_COOKIE.to_string()]
+  } else {
+    Vec::new()
+  };
+  let url: String = search_url(query, params)?;
+  Ok(dump_flat_page(&url, page, limit, &extra_args).await?.into_search_page(params, page, limit))
+}
+
+// metadata of the url, which may be a playlist; see `dump_json` for a single entry
+pub async fn dump_info(url: &str) -> Result<YtDlpInfo, String> {
+  let args: Vec<String> = vec![
+    // one JSON document even for urls that list several entries, which are not looked up one by one
+    "--dump-single-json".to_string(),
+    "--flat-playlist".to_string(),
+    "--no-playlist".to_string(),
+    "--skip-download".to_string(),
+    "--no-warnings".to_string(),
+    url.to_string(),
+  ];
+  let output = run_sidecar(yt_dlp_sidecar(), &args).await?;
+  parse_info_json(&output.stdout)
+}
+
+pub async fn dump_json(url: &str) -> Result<YtDlpInfo, String> {
+  dump_info(url).await?.single_entry(url)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::PathBuf;
+
+  fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_fixtures").join(name);
+    std::fs::read_to_string(path).unwrap()
+  }
+
+  #[test]
+  fn test_parse_video_fixture() {
+    let audio = parse_info_json(&fixture("yt_dlp_video.json")).unwrap().into_remote_audio("https://youtu.be/dQw4w9WgXcQ".to_string());
+    assert_eq!(audio.extractor.as_deref(), Some("youtube"));
+    assert_eq!(audio.title.as_deref(), Some("Field Recordings - Morning Birds (Full Album)"));
+    assert_eq!(audio.channel.as_deref(), Some("Quiet Field Archive"));
+    assert_eq!(audio.duration_seconds, Some(1262));
+    assert_eq!(audio.duration.as_deref(), Some("21:02"));
+    assert_eq!(audio.views.as_deref(), Some("48213"));
+    assert_eq!(audio.upload_date.as_deref(), Some("2023-04-17"));
+    assert_eq!(audio.license.as_deref(), Some("Creative Commons Attribution license (reuse allowed)"));
+    assert_eq!(audio.webpage_url.as_deref(), Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ"));
+    assert_eq!(audio.tags, vec!["field recording", "birdsong", "ambient"]);
+    assert_eq!(audio.thumbnail.as_deref(), Some("https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg"));
+    assert_eq!(audio.thumbnails.len(), 3);
+    assert_eq!(audio.thumbnails[0].width, Some(120));
+    assert!(audio.description.unwrap().starts_with("Recorded at dawn"));
+    assert_eq!(audio.url, "https://youtu.be/dQw4w9WgXcQ");
+  }
+
+  #[test]
+  fn test_parse_video_fixture_chapters() {
+    let audio = parse_info_json(&fixture("yt_dlp_video.json")).unwrap().into_remote_audio(String::new());
+    assert_eq!(audio.chapters, vec![
+      RemoteChapter { title: "Before Sunrise".to_string(), start_time: 0.0, end_time: 415.0 },
+      RemoteChapter { title: "Chorus".to_string(), start_time: 415.0, end_time: 903.5 },
+      RemoteChapter { title: "Chapter 3".to_string(), start_time: 903.5, end_time: 1262.0 },
+    ]);
+  }
+
+  #[test]
+  fn test_parse_sparse_fixture() {
+    let audio = parse_info_json(&fixture("yt_dlp_sparse.json")).unwrap().into_remote_audio("https://example.com/a".to_string());
+    assert_eq!(audio.title.as_deref(), Some("untitled"));
+    // falls back to the channel and the best listed thumbnail
+    assert_eq!(audio.channel.as_deref(), Some("Some Channel"));
+    assert_eq!(audio.thumbnail.as_deref(), Some("https://example.com/large.jpg"));
+    assert_eq!(audio.duration_seconds, None);
+    assert_eq!(audio.upload_date, None);
+    assert!(audio.tags.is_empty());
+    assert!(audio.chapters.is_empty());
+  }
+
+  #[test]
+  fn test_parse_soundcloud_fixture() {
+    let url = "https://soundcloud.com/quietfieldarchive/dawn-chorus-edit".to_string();
+    let audio = parse_info_json(&fixture("yt_dlp_soundcloud.json")).unwrap().into_remote_audio(url);
+    assert_eq!(audio.extractor.as_deref(), Some("soundcloud"));
+    assert_eq!(audio.video_id.as_deref(), Some("1654932817"));
+    assert_eq!(audio.channel.as_deref(), Some("Quiet Field Archive"));
+    assert_eq!(audio.duration_seconds, Some(245));
+    assert_eq!(audio.upload_date.as_deref(), Some("2023-10-19"));
+    assert_eq!(audio.thumbnail.as_deref(), Some("https://i1.sndcdn.com/artworks-000123456789-abcdef-original.jpg"));
+  }
+
+  #[test]
+  fn test_album_is_not_a_single_entry() {
+    let url = "https://quietfieldarchive.bandcamp.com/album/morning-sessions";
+    let info = parse_info_json(&fixture("yt_dlp_bandcamp_album.json")).unwrap();
+    assert_eq!(info.kind.as_deref(), Some("playlist"));
+    let err = info.single_entry(url).unwrap_err();
+    assert!(err.contains("3 entries"), "{}", err);
+
+    let track = parse_info_json(&fixture("yt_dlp_soundcloud.json")).unwrap();
+    assert_eq!(track.single_entry(url).unwrap().id.as_deref(), Some("1654932817"));
+    // output without a type counts as a single entry
+    assert!(parse_info_json(r#"{"id": "a"}"#).unwrap().single_entry(url).is_ok());
+  }
+
+  #[test]
+  fn test_parse_printed_info() {
+    let lines: Vec<String> = vec![
+      fixture("yt_dlp_soundcloud.json").replace('\n', ""),
+      "/audio/dawn chorus.download.opus".to_string(),
+    ];
+    let info = parse_printed_info(&lines).unwrap();
+    assert_eq!(info.id.as_deref(), Some("1654932817"));
+    assert!(parse_printed_info(&lines[1..]).is_err());
+  }
+
+  #[test]
+  fn test_parse_playlist_fixture() {
+    let collection = parse_playlist_json(&fixture("yt_dlp_playlist.json")).unwrap()
+      .into_collection(YouTubeUrlKind::Playlist, 1, 3);
+    assert_eq!(collection.title.as_deref(), Some("Morning Sessions"));
+    assert_eq!(collection.uploader.as_deref(), Some("Quiet Field Archive"));
+    assert_eq!(collection.total, Some(5));
+    assert!(collection.has_more);
+    assert_eq!(collection.entries.len(), 3);
+    assert_eq!(collection.entries[0].video_id.as_deref(), Some("dQw4w9WgXcQ"));
+    assert_eq!(collection.entries[0].extractor.as_deref(), Some("youtube"));
+    assert_eq!(collection.entries[0].url, "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+    assert_eq!(collection.entries[0].duration.as_deref(), Some("21:02"));
+    // entries without a url are built from the id
+    assert_eq!(collection.entries[2].url, "https://www.youtube.com/watch?v=9bZkp7q19f0");
+
+    let last_page = parse_playlist_json(&fixture("yt_dlp_playlist.json")).unwrap()
+      .into_collection(YouTubeUrlKind::Playlist, 2, 3);
+    assert!(!last_page.has_more);
+  }
+
+  #[test]
+  fn test_parse_search_fixture() {
+    let params = YouTubeSearchParams { include_channels: true, ..Default::default() };
+    let search_page = parse_playlist_json(&fixture("yt_dlp_search.json")).unwrap().into_search_page(&params, 1, 4);
+    let kinds: Vec<YouTubeUrlKind> = search_page.results.iter().map(|item| item.kind).collect();
+    // the playlist was not asked for
+    assert_eq!(kinds, vec![YouTubeUrlKind::Video, YouTubeUrlKind::Channel, YouTubeUrlKind::Video]);
+    assert_eq!(search_page.results[0].entry.video_id.as_deref(), Some("dQw4w9WgXcQ"));
+    assert_eq!(search_page.results[0].entry.duration.as_deref(), Some("21:02"));
+    assert_eq!(search_page.results[1].entry.title.as_deref(), Some("Quiet Field Archive"));
+    // a full page without a total may be followed by more
+    assert_eq!(search_page.next_page_token.as_deref(), Some("2"));
+
+    let last_page = parse_playlist_json(&fixture("yt_dlp_search.json")).unwrap().into_search_page(&YouTubeSearchParams::default(), 3, 20);
+    assert_eq!(last_page.results.len(), 2);
+    assert_eq!(last_page.next_page_token, None);
+  }
+
+  #[test]
+  fn test_search_filter_param() {
+    assert_eq!(search_filter_param(&YouTubeSearchParams::default()).as_deref(), Some("EgIQAQ=="));
+    let mixed = YouTubeSearchParams { include_playlists: true, ..Default::default() };
+    assert_eq!(search_filter_param(&mixed), None);
+    let by_views = YouTubeSearchParams { sort: Some(SearchSort::Views), include_channels: true, ..Default::default() };
+    assert_eq!(search_filter_param(&by_views).as_deref(), Some("CAM="));
+    let filtered = YouTubeSearchParams {
+      sort: Some(SearchSort::Date),
+      upload_date: Some(SearchUploadDate::ThisWeek),
+      duration: Some(SearchDuration::Long),
+      ..Default::default()
+    };
+    // 08 02 12 06 08 03 10 01 18 02
+    assert_eq!(search_filter_param(&filtered).as_deref(), Some("CAISBggDEAEYAg=="));
+  }
+
+  #[test]
+  fn test_search_url() {
+    let url = search_url("rain & thunder", &YouTubeSearchParams::default()).unwrap();
+    assert_eq!(url, "https://www.youtube.com/results?search_query=rain+%26+thunder&sp=EgIQAQ%3D%3D");
+  }
+
+  #[test]
+  fn test_parse_page_token() {
+    assert_eq!(parse_page_token(None), Ok(1));
+    assert_eq!(parse_page_token(Some("3")), Ok(3));
+    assert!(parse_page_token(Some("0")).is_err());
+    assert!(parse_page_token(Some("next")).is_err());
+  }
+
+  #[test]
+  fn test_classify_url() {
+    assert_eq!(classify_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ"), Ok(YouTubeUrlKind::Video));
+    assert_eq!(classify_url("https://youtu.be/dQw4w9WgXcQ"), Ok(YouTubeUrlKind::Video));
+    assert_eq!(classify_url("https://www.youtube.com/playlist?list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG"), Ok(YouTubeUrlKind::Playlist));
+    assert_eq!(classify_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLx0sYbCqOb8TBPRdmBHs5Iftvv9TPboYG"), Ok(YouTubeUrlKind::Playlist));
+    assert_eq!(classify_url("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ"), Ok(YouTubeUrlKind::Mix));
+    assert_eq!(classify_url("https://www.youtube.com/@quietfieldarchive"), Ok(YouTubeUrlKind::Channel));
+    assert_eq!(classify_url("https://www.youtube.com/channel/UCq9Xb2dFtfVYpxu1MUVNKYQ/videos"), Ok(YouTubeUrlKind::Channel));
+    assert!(classify_url("not a url").is_err());
+  }
+
+  #[test]
+  fn test_extract_video_id() {
+    assert_eq!(extract_video_id("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"), Some("dQw4w9WgXcQ".to_string()));
+    assert_eq!(extract_video_id("https://youtu.be/dQw4w9WgXcQ?si=abc"), Some("dQw4w9WgXcQ".to_string()));
+    assert_eq!(extract_video_id("https://m.youtube.com/shorts/dQw4w9WgXcQ"), Some("dQw4w9WgXcQ".to_string()));
+    assert_eq!(extract_video_id("https://www.youtube.com/playlist?list=PL1"), None);
+    assert_eq!(extract_video_id("https://example.com/watch?v=dQw4w9WgXcQ"), None);
+  }
+
+  #[test]
+  fn test_collection_url() {
+    assert_eq!(
+      collection_url("https://www.youtube.com/@quietfieldarchive", YouTubeUrlKind::Channel),
+      "https://www.youtube.com/@quietfieldarchive/videos"
+    );
+    assert_eq!(
+      collection_url("https://www.youtube.com/channel/UCq9Xb2dFtfVYpxu1MUVNKYQ/", YouTubeUrlKind::Channel),
+      "https://www.youtube.com/channel/UCq9Xb2dFtfVYpxu1MUVNKYQ/videos"
+    );
+    assert_eq!(
+      collection_url("https://www.youtube.com/@quietfieldarchive/streams", YouTubeUrlKind::Channel),
+      "https://www.youtube.com/@quietfieldarchive/streams"
+    );
+    assert_eq!(
+      collection_url("https://www.youtube.com/playlist?list=PL1", YouTubeUrlKind::Playlist),
+      "https://www.youtube.com/playlist?list=PL1"
+    );
+  }
+
+  #[test]
+  fn test_parse_invalid_json() {
+    assert!(parse_info_json("ERROR: Unsupported URL").is_err());
+  }
+
+  #[test]
+  fn test_format_upload_date() {
+    assert_eq!(format_upload_date("20240131"), Some("2024-01-31".to_string()));
+    assert_eq!(format_upload_date("2024-01"), None);
+    assert_eq!(format_upload_date("NA"), None);
+  }
+}
@@ -48,6 +48,26 @@ fn get_db_path() -> String {
     db_dir.to_str().unwrap().to_string() + "/chamberdb.sqlite"
 }
 
+#[cfg(test)]
+pub mod test_utils {
+    use std::sync::{Mutex, MutexGuard, OnceLock};
+    use tempfile::TempDir;
+
+    static DATABASE_LOCK: Mutex<()> = Mutex::new(());
+    static TEST_HOME: OnceLock<TempDir> = OnceLock::new();
+
+    // points the home directory, and with it the database, at a temporary directory and starts
+    // from an empty, migrated database. Tests touching the database hold the guard while they run
+    pub fn fresh_database() -> MutexGuard<'static, ()> {
+        let guard: MutexGuard<'static, ()> = DATABASE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let home: &TempDir = TEST_HOME.get_or_init(|| tempfile::tempdir().unwrap());
+        std::env::set_var("HOME", home.path());
+        let _ = super::fs::remove_file(super::get_db_path());
+        super::init();
+        guard
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -56,6 +76,7 @@ mod tests {
 
     #[test]
     fn test_get_db_path() {
+        let _database = test_utils::fresh_database();
         let dir = tempdir().unwrap();
         env::set_current_dir(&dir).unwrap();
         let db_path = get_db_path();
@@ -64,6 +85,7 @@ mod tests {
 
     #[test]
     fn test_create_db_file() {
+        let _database = test_utils::fresh_database();
         let dir = tempdir().unwrap();
         env::set_current_dir(&dir).unwrap();
         let db_path = get_db_path();
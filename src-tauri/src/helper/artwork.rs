@@ -0,0 +1,214 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use image::{DynamicImage, ImageFormat};
+use crate::helper::constants::artwork_cache_path;
+use crate::helper::files::hash_bytes;
+
+// square sizes (in px) of the resized variants generated for every artwork
+pub const ARTWORK_SIZES: [u32; 2] = [64, 256];
+
+pub const ARTWORK_URI_SCHEME: &str = "artwork";
+
+fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn original_extension(format: ImageFormat) -> Result<&'static str, String> {
+    match format {
+        ImageFormat::Jpeg => Ok("jpg"),
+        ImageFormat::Png => Ok("png"),
+        ImageFormat::WebP => Ok("webp"),
+        _ => Err("Unsupported artwork format".to_string()),
+    }
+}
+
+// stores the image under its content hash along with its resized variants and returns the hash.
+// storing the same image twice is a no-op
+pub fn store_artwork_in(cache_dir: &Path, bytes: &[u8]) -> Result<String, String> {
+    let format: ImageFormat = image::guess_format(bytes).map_err(|e| format!("Unsupported artwork: {}", e))?;
+    let extension: &str = original_extension(format)?;
+    let hash: String = hash_bytes(bytes);
+    let artwork_dir: PathBuf = cache_dir.join(&hash);
+
+    let original_path: PathBuf = artwork_dir.join(format!("original.{}", extension));
+    if original_path.exists() {
+        return Ok(hash);
+    }
+
+    let decoded: DynamicImage = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| format!("Unable to decode artwork: {}", e))?;
+
+    fs::create_dir_all(&artwork_dir).map_err(|e| format!("Unable to create artwork directory: {}", e))?;
+    for size in ARTWORK_SIZES {
+        let resized: DynamicImage = DynamicImage::ImageRgb8(decoded.thumbnail(size, size).to_rgb8());
+        resized.save_with_format(artwork_dir.join(format!("{}.jpg", size)), ImageFormat::Jpeg)
+            .map_err(|e| format!("Unable to write artwork variant: {}", e))?;
+    }
+    // the original is written last so that its presence marks a complete entry
+    fs::write(&original_path, bytes).map_err(|e| format!("Unable to write artwork: {}", e))?;
+
+    Ok(hash)
+}
+
+// JPEG and PNG are passed through, other images (e.g. WebP thumbnails) are converted to JPEG
+// since most tag formats and players only read those two
+pub fn embeddable_image(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    let format: ImageFormat = image::guess_format(&bytes).map_err(|e| format!("Unsupported artwork: {}", e))?;
+    if matches!(format, ImageFormat::Jpeg | ImageFormat::Png) {
+        return Ok(bytes);
+    }
+    let decoded: DynamicImage = image::load_from_memory_with_format(&bytes, format)
+        .map_err(|e| format!("Unable to decode artwork: {}", e))?;
+    let mut converted: Vec<u8> = Vec::new();
+    DynamicImage::ImageRgb8(decoded.to_rgb8())
+        .write_to(&mut std::io::Cursor::new(&mut converted), ImageFormat::Jpeg)
+        .map_err(|e| format!("Unable to convert artwork: {}", e))?;
+    Ok(converted)
+}
+
+pub fn store_artwork(bytes: &[u8]) -> Result<String, String> {
+    store_artwork_in(&artwork_cache_path(), bytes)
+}
+
+// path to a cached artwork; size None is the original, otherwise one of ARTWORK_SIZES
+pub fn artwork_file_in(cache_dir: &Path, hash: &str, size: Option<u32>) -> Result<PathBuf, String> {
+    if !is_valid_hash(hash) {
+        return Err(format!("Invalid artwork id: {}", hash));
+    }
+    let artwork_dir: PathBuf = cache_dir.join(hash);
+
+    let artwork_path: Option<PathBuf> = match size {
+        Some(size) if ARTWORK_SIZES.contains(&size) => Some(artwork_dir.join(format!("{}.jpg", size))),
+        Some(size) => return Err(format!("Unsupported artwork size: {}", size)),
+        None => ["jpg", "png", "webp"].iter()
+            .map(|ext| artwork_dir.join(format!("original.{}", ext)))
+            .find(|path| path.exists()),
+    };
+
+    match artwork_path {
+        Some(path) if path.exists() => Ok(path),
+        _ => Err(format!("Artwork not found: {}", hash)),
+    }
+}
+
+pub fn artwork_file(hash: &str, size: Option<u32>) -> Result<PathBuf, String> {
+    artwork_file_in(&artwork_cache_path(), hash, size)
+}
+
+// builds the URI the frontend uses to display an artwork, e.g. artwork://localhost/<hash>/256
+pub fn artwork_uri(hash: &str, size: Option<u32>) -> String {
+    match size {
+        Some(size) => format!("{}://localhost/{}/{}", ARTWORK_URI_SCHEME, hash, size),
+        None => format!("{}://localhost/{}/original", ARTWORK_URI_SCHEME, hash),
+    }
+}
+
+// parses the hash and size from an artwork URI. Windows serves custom protocols as
+// https://artwork.localhost/..., so only the path of the URI is considered
+pub fn parse_artwork_uri(uri: &str) -> Result<(String, Option<u32>), String> {
+    let without_scheme: &str = uri.split_once("://").map(|(_, rest)| rest).unwrap_or(uri);
+    let path: &str = without_scheme.split_once('/').map(|(_, path)| path).unwrap_or("");
+    let path: &str = path.split(['?', '#']).next().unwrap_or("");
+
+    let mut segments = path.split('/').filter(|s| !s.is_empty());
+    let hash: String = segments.next().ok_or("Missing artwork id")?.to_string();
+    let size: Option<u32> = match segments.next() {
+        None | Some("original") => None,
+        Some(size) => Some(size.parse::<u32>().map_err(|_| format!("Invalid artwork size: {}", size))?),
+    };
+
+    Ok((hash, size))
+}
+
+// resolves an artwork URI to the image bytes and their mime type, used by the URI scheme handler
+pub fn resolve_artwork_uri(uri: &str) -> Result<(Vec<u8>, String), String> {
+    let (hash, size) = parse_artwork_uri(uri)?;
+    let path: PathBuf = artwork_file(&hash, size)?;
+    let bytes: Vec<u8> = fs::read(&path).map_err(|e| format!("Unable to read artwork: {}", e))?;
+    let mime: String = mime_guess::from_path(&path).first_or_octet_stream().essence_str().to_string();
+    Ok((bytes, mime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let img = DynamicImage::new_rgb8(width, height);
+        let mut bytes: Vec<u8> = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_store_artwork_creates_variants() {
+        let dir = tempdir().unwrap();
+        let bytes = png_bytes(600, 300);
+        let hash = store_artwork_in(dir.path(), &bytes).unwrap();
+
+        assert_eq!(hash, hash_bytes(&bytes));
+        let original = artwork_file_in(dir.path(), &hash, None).unwrap();
+        assert_eq!(fs::read(original).unwrap(), bytes);
+
+        let variant = image::open(artwork_file_in(dir.path(), &hash, Some(256)).unwrap()).unwrap();
+        assert_eq!((variant.width(), variant.height()), (256, 128));
+    }
+
+    #[test]
+    fn test_store_artwork_twice_is_idempotent() {
+        let dir = tempdir().unwrap();
+        let bytes = png_bytes(10, 10);
+        let first = store_artwork_in(dir.path(), &bytes).unwrap();
+        let second = store_artwork_in(dir.path(), &bytes).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_store_artwork_invalid_image() {
+        let dir = tempdir().unwrap();
+        assert!(store_artwork_in(dir.path(), b"not an image at all").is_err());
+    }
+
+    #[test]
+    fn test_artwork_file_rejects_path_traversal() {
+        let dir = tempdir().unwrap();
+        assert!(artwork_file_in(dir.path(), "../../etc/passwd", None).is_err());
+    }
+
+    #[test]
+    fn test_artwork_file_unsupported_size() {
+        let dir = tempdir().unwrap();
+        let hash = store_artwork_in(dir.path(), &png_bytes(10, 10)).unwrap();
+        assert!(artwork_file_in(dir.path(), &hash, Some(100)).is_err());
+    }
+
+    #[test]
+    fn test_embeddable_image() {
+        let png = png_bytes(8, 8);
+        assert_eq!(embeddable_image(png.clone()).unwrap(), png);
+
+        let mut webp: Vec<u8> = Vec::new();
+        DynamicImage::new_rgb8(8, 8).write_to(&mut Cursor::new(&mut webp), ImageFormat::WebP).unwrap();
+        let converted = embeddable_image(webp).unwrap();
+        assert_eq!(image::guess_format(&converted).unwrap(), ImageFormat::Jpeg);
+
+        assert!(embeddable_image(b"not an image".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_parse_artwork_uri() {
+        let hash = "a".repeat(64);
+        assert_eq!(parse_artwork_uri(&artwork_uri(&hash, Some(256))).unwrap(), (hash.clone(), Some(256)));
+        assert_eq!(parse_artwork_uri(&artwork_uri(&hash, None)).unwrap(), (hash.clone(), None));
+        assert_eq!(parse_artwork_uri(&format!("https://artwork.localhost/{}/64?v=1", hash)).unwrap(), (hash.clone(), Some(64)));
+        assert_eq!(parse_artwork_uri(&format!("artwork://localhost/{}", hash)).unwrap(), (hash, None));
+    }
+
+    #[test]
+    fn test_parse_artwork_uri_invalid() {
+        assert!(parse_artwork_uri("artwork://localhost/").is_err());
+        assert!(parse_artwork_uri("artwork://localhost/abc/large").is_err());
+    }
+}
@@ -4,4 +4,18 @@ pub fn audio_store_path() -> std::path::PathBuf {
   let mut path = home::home_dir().unwrap();
   path.push("audio_store");
   path
-}
\ No newline at end of file
+}
+
+pub fn cache_path() -> std::path::PathBuf {
+  let mut path = home::home_dir().unwrap();
+  path.push("chamber_cache");
+  path
+}
+
+pub fn artwork_cache_path() -> std::path::PathBuf {
+  cache_path().join("artwork")
+}
+
+pub fn waveform_cache_path() -> std::path::PathBuf {
+  cache_path().join("waveforms")
+}
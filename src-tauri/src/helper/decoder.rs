@@ -0,0 +1,609 @@
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{
+    CodecType, Decoder, DecoderOptions, CODEC_TYPE_FLAC, CODEC_TYPE_NULL, CODEC_TYPE_PCM_S16LE,
+    CODEC_TYPE_PCM_S24LE, CODEC_TYPE_PCM_S32LE, CODEC_TYPE_PCM_U8,
+};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::sample::Sample;
+use symphonia::core::conv::ConvertibleSample;
+use crate::helper::sidecar::{ffmpeg_sidecar, stream_sidecar};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // number of frames (samples per channel), None if the container does not declare it
+    pub total_frames: Option<u64>,
+}
+
+impl AudioInfo {
+    pub fn duration_secs(&self) -> Option<f64> {
+        self.total_frames.map(|frames| frames as f64 / self.sample_rate as f64)
+    }
+}
+
+pub struct DecodedAudio {
+    pub sample_rate: u32,
+    pub channels: u16,
+    // interleaved samples in [-1.0, 1.0]
+    pub samples: Vec<f32>,
+}
+
+impl DecodedAudio {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration_secs(&self) -> f64 {
+        self.frames() as f64 / self.sample_rate as f64
+    }
+
+    pub fn to_mono(&self) -> Vec<f32> {
+        let channels: usize = self.channels.max(1) as usize;
+        self.samples.chunks(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+
+    pub fn info(&self) -> AudioInfo {
+        AudioInfo {
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+            total_frames: Some(self.frames() as u64),
+        }
+    }
+}
+
+// consumes decoded audio chunk by chunk, so an analysis never needs the whole file in memory
+pub trait SampleSink: Send + 'static {
+    type Output: Send + 'static;
+
+    // interleaved samples in [-1.0, 1.0], always whole frames. Break stops decoding early
+    fn push(&mut self, samples: &[f32]) -> ControlFlow<()>;
+
+    // called once decoding ended; `info.total_frames` counts the frames that were decoded
+    fn finish(self, info: &AudioInfo) -> Result<Self::Output, String>;
+}
+
+// collects the whole file, for callers that really need every sample at once
+impl SampleSink for DecodedAudio {
+    type Output = DecodedAudio;
+
+    fn push(&mut self, samples: &[f32]) -> ControlFlow<()> {
+        self.samples.extend_from_slice(samples);
+        ControlFlow::Continue(())
+    }
+
+    fn finish(self, _info: &AudioInfo) -> Result<DecodedAudio, String> {
+        Ok(self)
+    }
+}
+
+// lossless containers that can be trimmed natively without going through ffmpeg
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LosslessFormat {
+    Wav,
+    Flac,
+}
+
+impl LosslessFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LosslessFormat::Wav => "wav",
+            LosslessFormat::Flac => "flac",
+        }
+    }
+}
+
+// layout of an integer PCM WAV or FLAC file, whose samples decode exactly as stored
+#[derive(Debug, Clone, PartialEq)]
+pub struct PcmSpec {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u32,
+    pub format: LosslessFormat,
+    pub total_frames: Option<u64>,
+}
+
+struct OpenedTrack {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    info: AudioInfo,
+    codec: CodecType,
+    bits_per_sample: Option<u32>,
+}
+
+fn open_track(file_path: &str) -> Result<OpenedTrack, String> {
+    let file: File = File::open(file_path).map_err(|e| format!("Unable to open file: {}", e))?;
+    let source = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = Path::new(file_path).extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    // gapless playback trims encoder delay and padding, which makes durations sample accurate
+    let format_options = FormatOptions { enable_gapless: true, ..Default::default() };
+    let probed = symphonia::default::get_probe()
+        .format(&hint, source, &format_options, &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported audio format: {}", e))?;
+    let format: Box<dyn FormatReader> = probed.format;
+
+    let track = format.tracks().iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No audio track found")?;
+    let track_id: u32 = track.id;
+
+    let decoder: Box<dyn Decoder> = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Unsupported codec: {}", e))?;
+
+    let info = AudioInfo {
+        sample_rate: track.codec_params.sample_rate.ok_or("Unknown sample rate")?,
+        channels: track.codec_params.channels.map(|c| c.count() as u16).unwrap_or(1),
+        total_frames: track.codec_params.n_frames,
+    };
+
+    let codec: CodecType = track.codec_params.codec;
+    let bits_per_sample: Option<u32> = track.codec_params.bits_per_sample;
+
+    Ok(OpenedTrack { format, decoder, track_id, info, codec, bits_per_sample })
+}
+
+fn decode_track<S, F>(opened: OpenedTrack, mut on_samples: F) -> Result<AudioInfo, String>
+where
+    S: Sample + ConvertibleSample,
+    F: FnMut(&[S], u16) -> ControlFlow<()>,
+{
+    let OpenedTrack { mut format, mut decoder, track_id, mut info, .. } = opened;
+    let mut sample_buffer: Option<SampleBuffer<S>> = None;
+    let mut decoded_frames: u64 = 0;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(format!("Error reading audio: {}", err)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // corrupt frames are skipped, like players do
+            Err(Error::DecodeError(_)) => continue,
+            Err(err) => return Err(format!("Error decoding audio: {}", err)),
+        };
+
+        let spec = *decoded.spec();
+        let channels: u16 = spec.channels.count() as u16;
+        let buffer = match sample_buffer.as_mut() {
+            Some(buffer) if buffer.capacity() >= decoded.capacity() * channels as usize => buffer,
+            _ => sample_buffer.insert(SampleBuffer::<S>::new(decoded.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(decoded);
+
+        info.channels = channels;
+        decoded_frames += (buffer.samples().len() / channels.max(1) as usize) as u64;
+        if on_samples(buffer.samples(), channels).is_break() {
+            break;
+        }
+    }
+
+    info.total_frames = Some(decoded_frames);
+    Ok(info)
+}
+
+// decodes the file packet by packet, handing interleaved f32 samples to `on_samples`.
+// Returns the stream info with total_frames set to the number of frames actually decoded
+pub fn decode_audio_file<F>(file_path: &str, mut on_samples: F) -> Result<AudioInfo, String>
+where
+    F: FnMut(&[f32], u16),
+{
+    decode_track::<f32, _>(open_track(file_path)?, |chunk, channels| {
+        on_samples(chunk, channels);
+        ControlFlow::Continue(())
+    })
+}
+
+fn lossless_format_of(opened: &OpenedTrack) -> Option<LosslessFormat> {
+    match opened.codec {
+        CODEC_TYPE_FLAC => Some(LosslessFormat::Flac),
+        CODEC_TYPE_PCM_U8 | CODEC_TYPE_PCM_S16LE | CODEC_TYPE_PCM_S24LE | CODEC_TYPE_PCM_S32LE => Some(LosslessFormat::Wav),
+        _ => None,
+    }
+}
+
+// the lossless format of the file, None for lossy or float files and files that cannot be opened
+pub fn lossless_format(file_path: &str) -> Option<LosslessFormat> {
+    open_track(file_path).ok().and_then(|opened| lossless_format_of(&opened))
+}
+
+fn pcm_spec_of(opened: &OpenedTrack) -> Result<PcmSpec, String> {
+    Ok(PcmSpec {
+        sample_rate: opened.info.sample_rate,
+        channels: opened.info.channels,
+        bits_per_sample: opened.bits_per_sample.ok_or("Unknown bit depth")?,
+        format: lossless_format_of(opened).ok_or("Native trimming only supports integer PCM WAV and FLAC files")?,
+        total_frames: opened.info.total_frames,
+    })
+}
+
+pub fn pcm_spec(file_path: &str) -> Result<PcmSpec, String> {
+    pcm_spec_of(&open_track(file_path)?)
+}
+
+// decodes frames `start_frame..end_frame` of an integer PCM WAV or FLAC file without any conversion loss,
+// handing the interleaved samples to `on_samples` at the bit depth of the file. Seeks to the start and stops
+// at the end of the range. Returns the number of frames handed over
+pub fn decode_pcm_range<F>(file_path: &str, start_frame: u64, end_frame: u64, mut on_samples: F) -> Result<u64, String>
+where
+    F: FnMut(&[i32]) -> Result<(), String>,
+{
+    let opened: OpenedTrack = open_track(file_path)?;
+    let shift: u32 = 32 - pcm_spec_of(&opened)?.bits_per_sample;
+    let OpenedTrack { mut format, mut decoder, track_id, .. } = opened;
+
+    // when seeking fails the frames before the range are decoded and skipped
+    if start_frame > 0 && format.seek(SeekMode::Accurate, SeekTo::TimeStamp { ts: start_frame, track_id }).is_ok() {
+        decoder.reset();
+    }
+
+    let mut sample_buffer: Option<SampleBuffer<i32>> = None;
+    let mut samples: Vec<i32> = Vec::new();
+    let mut handed_over: u64 = 0;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(format!("Error reading audio: {}", err)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        // WAV and FLAC timestamps count frames
+        let position: u64 = packet.ts();
+        if position >= end_frame {
+            break;
+        }
+        if position + packet.dur() <= start_frame {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(Error::DecodeError(_)) => continue,
+            Err(err) => return Err(format!("Error decoding audio: {}", err)),
+        };
+        let spec = *decoded.spec();
+        let channels: usize = spec.channels.count().max(1);
+        let buffer = match sample_buffer.as_mut() {
+            Some(buffer) if buffer.capacity() >= decoded.capacity() * channels => buffer,
+            _ => sample_buffer.insert(SampleBuffer::<i32>::new(decoded.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(decoded);
+
+        let frames: u64 = (buffer.samples().len() / channels) as u64;
+        let from: u64 = start_frame.clamp(position, position + frames) - position;
+        let to: u64 = end_frame.clamp(position, position + frames) - position;
+        // samples are decoded full scale, shift them back to the bit depth of the file
+        samples.clear();
+        samples.extend(buffer.samples()[from as usize * channels..to as usize * channels].iter().map(|s| s >> shift));
+        on_samples(&samples)?;
+        handed_over += to - from;
+    }
+
+    Ok(handed_over)
+}
+
+// start time of every packet followed by the end time of the last one, in seconds.
+// A stream copy can only cut at these boundaries
+pub fn packet_boundaries(file_path: &str) -> Result<Vec<f64>, String> {
+    let OpenedTrack { mut format, track_id, .. } = open_track(file_path)?;
+    let time_base = format.tracks().iter()
+        .find(|track| track.id == track_id)
+        .and_then(|track| track.codec_params.time_base)
+        .ok_or("Unknown time base")?;
+
+    let mut boundaries: Vec<f64> = Vec::new();
+    let mut end: Option<f64> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(format!("Error reading audio: {}", err)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let start = time_base.calc_time(packet.ts());
+        let finish = time_base.calc_time(packet.ts() + packet.dur());
+        boundaries.push(start.seconds as f64 + start.frac);
+        end = Some(finish.seconds as f64 + finish.frac);
+    }
+    boundaries.extend(end);
+    Ok(boundaries)
+}
+
+pub fn decode_to_pcm(file_path: &str) -> Result<DecodedAudio, String> {
+    let mut samples: Vec<f32> = Vec::new();
+    let info: AudioInfo = decode_audio_file(file_path, |chunk, _| samples.extend_from_slice(chunk))?;
+
+    Ok(DecodedAudio {
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        samples,
+    })
+}
+
+// reads the stream parameters without decoding when the container declares its length
+pub fn probe_audio(file_path: &str) -> Result<AudioInfo, String> {
+    let info: AudioInfo = open_track(file_path)?.info;
+    if info.total_frames.is_some() {
+        return Ok(info);
+    }
+    decode_audio_file(file_path, |_, _| {})
+}
+
+pub fn probe_duration(file_path: &str) -> Result<f64, String> {
+    probe_audio(file_path)?.duration_secs().ok_or("Unable to determine duration".to_string())
+}
+
+// rate used when a file has to be decoded by the ffmpeg sidecar
+pub const FALLBACK_SAMPLE_RATE: u32 = 48000;
+
+// decodes natively into a sink made for the stream. A chunk with another channel count than the stream
+// declared fails the pass, so the ffmpeg fallback can downmix it consistently
+fn decode_natively<S, F>(file_path: &str, make_sink: &F) -> Result<(S, AudioInfo), String>
+where
+    S: SampleSink,
+    F: Fn(&AudioInfo) -> Result<S, String>,
+{
+    let opened: OpenedTrack = open_track(file_path)?;
+    let expected_channels: u16 = opened.info.channels;
+    let mut sink: S = make_sink(&opened.info)?;
+    let mut layout_changed: bool = false;
+    let info: AudioInfo = decode_track::<f32, _>(opened, |chunk, channels| {
+        if channels != expected_channels {
+            layout_changed = true;
+            return ControlFlow::Break(());
+        }
+        sink.push(chunk)
+    })?;
+    if layout_changed {
+        return Err("The channel layout changes within the stream".to_string());
+    }
+    Ok((sink, info))
+}
+
+// has the ffmpeg sidecar decode the file to raw f32 samples on stdout, feeding them to the sink as they arrive
+fn decode_with_ffmpeg<S, F>(file_path: &str, make_sink: &F) -> Result<(S, AudioInfo), String>
+where
+    S: SampleSink,
+    F: Fn(&AudioInfo) -> Result<S, String>,
+{
+    use lofty::file::AudioFile;
+
+    // keep the channel layout of the source, analysis results depend on it
+    let channels: u16 = lofty::read_from_path(file_path)
+        .ok()
+        .and_then(|tagged_file| tagged_file.properties().channels())
+        .map(|channels| channels as u16)
+        .unwrap_or(2);
+    let mut info = AudioInfo { sample_rate: FALLBACK_SAMPLE_RATE, channels, total_frames: None };
+    let mut sink: S = make_sink(&info)?;
+
+    let args: Vec<String> = vec![
+        "-v".to_string(), "error".to_string(),
+        "-nostdin".to_string(),
+        "-i".to_string(), file_path.to_string(),
+        "-ac".to_string(), channels.to_string(),
+        "-ar".to_string(), FALLBACK_SAMPLE_RATE.to_string(),
+        "-f".to_string(), "f32le".to_string(),
+        "-".to_string(),
+    ];
+
+    let frame_bytes: usize = 4 * channels as usize;
+    let mut pending: Vec<u8> = Vec::new();
+    let mut samples: Vec<f32> = Vec::new();
+    let mut frames: u64 = 0;
+    stream_sidecar(ffmpeg_sidecar(), &args, |bytes| {
+        // a read can end inside a frame, the rest of it comes with the next read
+        pending.extend_from_slice(bytes);
+        let whole: usize = pending.len() - pending.len() % frame_bytes;
+        samples.clear();
+        samples.extend(pending[..whole].chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])));
+        pending.drain(..whole);
+        frames += (whole / frame_bytes) as u64;
+        sink.push(&samples)
+    })?;
+
+    info.total_frames = Some(frames);
+    Ok((sink, info))
+}
+
+// decodes natively, falling back to the ffmpeg sidecar for formats the decoder does not support (e.g. opus).
+// `make_sink` is called with the stream parameters for every attempt, so a failed native pass leaves nothing
+// behind in the sink the fallback uses
+pub async fn decode_with_fallback<S, F>(file_path: &str, make_sink: F) -> Result<S::Output, String>
+where
+    S: SampleSink,
+    F: Fn(&AudioInfo) -> Result<S, String> + Send + 'static,
+{
+    let path: String = file_path.to_string();
+    tokio::task::spawn_blocking(move || {
+        let (sink, info) = match decode_natively(&path, &make_sink) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                eprintln!("Native decoding failed, falling back to ffmpeg: {}", err);
+                decode_with_ffmpeg(&path, &make_sink)?
+            }
+        };
+        sink.finish(&info)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+pub mod test_utils {
+    // writes a 16 bit WAV file from interleaved samples in [-1.0, 1.0]
+    pub fn write_test_wav(path: &std::path::Path, sample_rate: u32, channels: u16, samples: &[f32]) {
+        let spec = hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for sample in samples {
+            writer.write_sample((sample * i16::MAX as f32) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    pub fn sine(frequency: f32, sample_rate: u32, seconds: f32, amplitude: f32) -> Vec<f32> {
+        let frames: usize = (sample_rate as f32 * seconds) as usize;
+        (0..frames)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_utils::{sine, write_test_wav};
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    fn fixture_mp3() -> String {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/test_fixtures/test_audio_mp3.mp3")
+            .to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_decode_wav() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.wav");
+        let stereo: Vec<f32> = sine(440.0, 8000, 1.0, 0.5).iter().flat_map(|s| [*s, -*s]).collect();
+        write_test_wav(&file_path, 8000, 2, &stereo);
+
+        let decoded = decode_to_pcm(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(decoded.sample_rate, 8000);
+        assert_eq!(decoded.channels, 2);
+        assert_eq!(decoded.frames(), 8000);
+        assert!((decoded.samples[2] - stereo[2]).abs() < 0.001);
+        assert!(decoded.to_mono().iter().all(|s| s.abs() < 0.001));
+    }
+
+    #[test]
+    fn test_probe_duration_wav() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.wav");
+        write_test_wav(&file_path, 44100, 1, &sine(440.0, 44100, 2.5, 0.5));
+
+        let duration = probe_duration(file_path.to_str().unwrap()).unwrap();
+        assert!((duration - 2.5).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_decode_mp3_fixture() {
+        let path = fixture_mp3();
+        let decoded = decode_to_pcm(&path).unwrap();
+        assert!(decoded.frames() > 0);
+
+        let probed = probe_duration(&path).unwrap();
+        assert!((probed - decoded.duration_secs()).abs() < 0.05);
+    }
+
+    fn collect_range(file_path: &str, start_frame: u64, end_frame: u64) -> Vec<i32> {
+        let mut samples: Vec<i32> = Vec::new();
+        let frames: u64 = decode_pcm_range(file_path, start_frame, end_frame, |chunk| {
+            samples.extend_from_slice(chunk);
+            Ok(())
+        }).unwrap();
+        assert_eq!(frames as usize * 2, samples.len());
+        samples
+    }
+
+    #[test]
+    fn test_decode_pcm_range_wav() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.wav");
+        let stereo: Vec<f32> = sine(440.0, 8000, 1.0, 0.5).iter().flat_map(|s| [*s, -*s]).collect();
+        write_test_wav(&file_path, 8000, 2, &stereo);
+
+        let spec = pcm_spec(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(spec.format, LosslessFormat::Wav);
+        assert_eq!(spec.bits_per_sample, 16);
+        assert_eq!(spec.total_frames, Some(8000));
+
+        let samples = collect_range(file_path.to_str().unwrap(), 1000, 1500);
+        assert_eq!(samples.len(), 500 * 2);
+        // the samples are the 16 bit values written to the file
+        assert_eq!(samples[0], (stereo[2000] * i16::MAX as f32) as i16 as i32);
+        assert_eq!(samples[1], (stereo[2001] * i16::MAX as f32) as i16 as i32);
+        // seeking lands on the same samples as decoding from the start
+        assert_eq!(samples, collect_range(file_path.to_str().unwrap(), 0, 1500)[2000..]);
+
+        // ranges past the end are cut short
+        assert_eq!(collect_range(file_path.to_str().unwrap(), 7900, 9000).len(), 100 * 2);
+        assert!(collect_range(file_path.to_str().unwrap(), 9000, 9500).is_empty());
+    }
+
+    #[test]
+    fn test_decode_pcm_range_stops_at_the_end() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.wav");
+        write_test_wav(&file_path, 8000, 1, &sine(440.0, 8000, 10.0, 0.5));
+
+        let mut calls: usize = 0;
+        decode_pcm_range(file_path.to_str().unwrap(), 0, 100, |_| {
+            calls += 1;
+            Ok(())
+        }).unwrap();
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_decode_pcm_range_rejects_lossy() {
+        assert!(decode_pcm_range(&fixture_mp3(), 0, 100, |_| Ok(())).is_err());
+        assert_eq!(lossless_format(&fixture_mp3()), None);
+    }
+
+    #[test]
+    fn test_packet_boundaries() {
+        let path = fixture_mp3();
+        let boundaries = packet_boundaries(&path).unwrap();
+        assert!(boundaries.len() > 1);
+        assert!(boundaries.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((boundaries.last().unwrap() - probe_duration(&path).unwrap()).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_decode_unsupported_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.mp3");
+        std::fs::write(&file_path, b"definitely not audio").unwrap();
+        assert!(decode_to_pcm(file_path.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_decode_nonexistent_file() {
+        assert!(probe_audio("/nonexistent/file.wav").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use crate::helper::decoder::{decode_pcm_range, pcm_spec, PcmSpec};
+
+// hound keeps the RIFF sizes in 32 bits and does not check them, so larger files would wrap
+const MAX_WAV_DATA_BYTES: u64 = u32::MAX as u64 - 36;
+
+// hound writes WAVE_FORMAT_EXTENSIBLE for more than two channels or more than 16 bits
+pub fn wav_spec(spec: &PcmSpec) -> Result<WavSpec, String> {
+    if !matches!(spec.bits_per_sample, 8 | 16 | 24 | 32) {
+        return Err(format!("Unsupported WAV bit depth {}", spec.bits_per_sample));
+    }
+    Ok(WavSpec {
+        channels: spec.channels,
+        sample_rate: spec.sample_rate,
+        bits_per_sample: spec.bits_per_sample as u16,
+        sample_format: SampleFormat::Int,
+    })
+}
+
+pub fn check_wav_size(spec: &WavSpec, frames: u64) -> Result<(), String> {
+    let data_bytes: u64 = frames * spec.channels as u64 * (spec.bits_per_sample as u64 / 8);
+    if data_bytes > MAX_WAV_DATA_BYTES {
+        return Err(format!("The trimmed audio ({} bytes) does not fit in a WAV file", data_bytes));
+    }
+    Ok(())
+}
+
+// streams frames `start_frame..end_frame` of an integer PCM WAV or FLAC file into a WAV file with the
+// same layout and returns the number of frames written
+pub fn write_wav_range(source_path: &str, start_frame: u64, end_frame: u64, destination: &Path) -> Result<u64, String> {
+    let source: PcmSpec = pcm_spec(source_path)?;
+    let spec: WavSpec = wav_spec(&source)?;
+    let frames: u64 = end_frame.min(source.total_frames.unwrap_or(end_frame)).saturating_sub(start_frame);
+    check_wav_size(&spec, frames)?;
+
+    let mut writer: WavWriter<BufWriter<File>> = WavWriter::create(destination, spec)
+        .map_err(|e| format!("Unable to create file: {}", e))?;
+    let written: u64 = decode_pcm_range(source_path, start_frame, end_frame, |samples| {
+        for sample in samples {
+            writer.write_sample(*sample).map_err(|e| format!("Unable to write file: {}", e))?;
+        }
+        Ok(())
+    })?;
+    writer.finalize().map_err(|e| format!("Unable to write file: {}", e))?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::decoder::LosslessFormat;
+    use tempfile::tempdir;
+
+    fn samples(bits_per_sample: u16, channels: u16, frames: usize) -> Vec<i32> {
+        let max: f64 = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+        (0..frames * channels as usize)
+            .map(|i| {
+                let frame: f64 = (i / channels as usize) as f64;
+                ((frame * 0.031 * (1 + i % channels as usize) as f64).sin() * 0.6 * max) as i32
+            })
+            .collect()
+    }
+
+    fn write_source(path: &Path, bits_per_sample: u16, channels: u16, samples: &[i32]) {
+        let spec = WavSpec { channels, sample_rate: 44100, bits_per_sample, sample_format: SampleFormat::Int };
+        let mut writer = WavWriter::create(path, spec).unwrap();
+        for sample in samples {
+            writer.write_sample(*sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn read_samples(path: &Path) -> Vec<i32> {
+        hound::WavReader::open(path).unwrap().samples::<i32>().map(|s| s.unwrap()).collect()
+    }
+
+    // the format tag of the fmt chunk, 1 for plain PCM and 0xFFFE for WAVE_FORMAT_EXTENSIBLE
+    fn format_tag(path: &Path) -> u16 {
+        let bytes: Vec<u8> = std::fs::read(path).unwrap();
+        u16::from_le_bytes([bytes[20], bytes[21]])
+    }
+
+    #[test]
+    fn test_wav_range_round_trip() {
+        let dir = tempdir().unwrap();
+        for bits_per_sample in [8, 16, 24, 32] {
+            let original: Vec<i32> = samples(bits_per_sample, 2, 1000);
+            let source = dir.path().join(format!("source_{}.wav", bits_per_sample));
+            let destination = dir.path().join(format!("trimmed_{}.wav", bits_per_sample));
+            write_source(&source, bits_per_sample, 2, &original);
+
+            let written = write_wav_range(source.to_str().unwrap(), 0, 1000, &destination).unwrap();
+            assert_eq!(written, 1000);
+            assert!(read_samples(&destination) == original, "{} bit WAV differs", bits_per_sample);
+            assert_eq!(pcm_spec(destination.to_str().unwrap()).unwrap().format, LosslessFormat::Wav);
+        }
+    }
+
+    #[test]
+    fn test_wav_range_from_the_middle() {
+        let dir = tempdir().unwrap();
+        let original: Vec<i32> = samples(16, 2, 50000);
+        let source = dir.path().join("source.wav");
+        let destination = dir.path().join("trimmed.wav");
+        write_source(&source, 16, 2, &original);
+
+        let written = write_wav_range(source.to_str().unwrap(), 20000, 30000, &destination).unwrap();
+        assert_eq!(written, 10000);
+        assert!(read_samples(&destination) == original[40000..60000]);
+
+        // a range running past the end stops at the last frame
+        let written = write_wav_range(source.to_str().unwrap(), 49000, 60000, &destination).unwrap();
+        assert_eq!(written, 1000);
+        assert!(read_samples(&destination) == original[98000..]);
+    }
+
+    #[test]
+    fn test_wav_header_follows_the_layout() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source.wav");
+        let destination = dir.path().join("trimmed.wav");
+
+        write_source(&source, 24, 2, &samples(24, 2, 100));
+        write_wav_range(source.to_str().unwrap(), 0, 100, &destination).unwrap();
+        assert_eq!(format_tag(&destination), 0xFFFE);
+
+        write_source(&source, 16, 2, &samples(16, 2, 100));
+        write_wav_range(source.to_str().unwrap(), 0, 100, &destination).unwrap();
+        assert_eq!(format_tag(&destination), 1);
+    }
+
+    #[test]
+    fn test_wav_size_is_checked() {
+        let spec = WavSpec { channels: 2, sample_rate: 48000, bits_per_sample: 24, sample_format: SampleFormat::Int };
+        assert!(check_wav_size(&spec, 48000 * 3600).is_ok());
+        // seven and a half hours of 24 bit stereo are more than 4 GiB
+        assert!(check_wav_size(&spec, 48000 * 3600 * 8).is_err());
+    }
+
+    #[test]
+    fn test_wav_spec_rejects_odd_bit_depth() {
+        let spec = PcmSpec { sample_rate: 44100, channels: 1, bits_per_sample: 12, format: LosslessFormat::Wav, total_frames: None };
+        assert!(wav_spec(&spec).is_err());
+    }
+}
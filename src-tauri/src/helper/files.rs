@@ -1,7 +1,8 @@
-use std::fs::{self, File};
-use std::io::Read;
-use std::path::Path;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
 use mime_guess::from_path;
+use sha2::{Digest, Sha256};
 use crate::helper::constants::audio_store_path;
 
 pub fn get_file_type(file_path: &str) -> Result<String, String> {
@@ -69,6 +70,44 @@ pub fn trim_invalid_file_characters(path: &str) -> String {
     trimmed_path
 }
 
+// reserves `<dir>/<base_name>.<extension>`, adding a -1, -2, ... suffix while the path is taken.
+// the path is claimed by creating an empty file, so concurrent creators never get the same one;
+// the caller overwrites it, or removes it with `discard_output_path` when rendering fails
+pub fn next_available_path(dir: &Path, base_name: &str, extension: &str) -> Result<PathBuf, String> {
+    let mut counter = 0;
+    loop {
+        let file_stem: String = if counter == 0 { base_name.to_string() } else { format!("{}-{}", base_name, counter) };
+        let candidate: PathBuf = if extension.is_empty() { dir.join(file_stem) } else { dir.join(format!("{}.{}", file_stem, extension)) };
+
+        match OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(_) => return Ok(candidate),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => counter += 1,
+            Err(e) => return Err(format!("Unable to create {}: {}", candidate.display(), e)),
+        }
+    }
+}
+
+// removes a reserved output path after a failed render and passes the error on
+pub fn discard_output_path(path: &Path, err: String) -> String {
+    let _ = fs::remove_file(path);
+    err
+}
+
+// content hash used to key the artwork and analysis caches
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hash_file(file_path: &str) -> Result<String, String> {
+    let mut file: File = File::open(file_path)
+        .map_err(|e| format!("Unable to open file: {}", e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Unable to read file: {}", e))?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,4 +341,83 @@ mod tests {
         let result = delete_file_if_exists(&dir_path);
         assert!(result.is_err());
     }
+
+    // Tests for next_available_path
+    #[test]
+    fn test_next_available_path() {
+        let dir = tempdir().unwrap();
+        let path = next_available_path(dir.path(), "clip", "mp3").unwrap();
+        assert_eq!(path, dir.path().join("clip.mp3"));
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn test_next_available_path_with_duplicates() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("clip.mp3")).unwrap();
+        File::create(dir.path().join("clip-1.mp3")).unwrap();
+
+        let path = next_available_path(dir.path(), "clip", "mp3").unwrap();
+        assert_eq!(path, dir.path().join("clip-2.mp3"));
+    }
+
+    #[test]
+    fn test_next_available_path_reserves() {
+        let dir = tempdir().unwrap();
+        let first = next_available_path(dir.path(), "clip", "mp3").unwrap();
+        let second = next_available_path(dir.path(), "clip", "mp3").unwrap();
+        assert_eq!(first, dir.path().join("clip.mp3"));
+        assert_eq!(second, dir.path().join("clip-1.mp3"));
+    }
+
+    #[test]
+    fn test_next_available_path_concurrent() {
+        let dir = tempdir().unwrap();
+        let handles: Vec<_> = (0..16).map(|_| {
+            let dir = dir.path().to_path_buf();
+            std::thread::spawn(move || next_available_path(&dir, "clip", "wav").unwrap())
+        }).collect();
+        let mut paths: Vec<PathBuf> = handles.into_iter().map(|handle| handle.join().unwrap()).collect();
+        paths.sort();
+        paths.dedup();
+        assert_eq!(paths.len(), 16);
+    }
+
+    #[test]
+    fn test_next_available_path_no_extension() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("clip")).unwrap();
+        let path = next_available_path(dir.path(), "clip", "").unwrap();
+        assert_eq!(path, dir.path().join("clip-1"));
+    }
+
+    #[test]
+    fn test_discard_output_path() {
+        let dir = tempdir().unwrap();
+        let path = next_available_path(dir.path(), "clip", "mp3").unwrap();
+        assert_eq!(discard_output_path(&path, "failed".to_string()), "failed");
+        assert!(!path.exists());
+    }
+
+    // Tests for hash_bytes and hash_file
+    #[test]
+    fn test_hash_bytes() {
+        assert_eq!(hash_bytes(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_hash_file_matches_hash_bytes() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, b"Hello, world!").unwrap();
+
+        let hash = hash_file(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(hash, hash_bytes(b"Hello, world!"));
+    }
+
+    #[test]
+    fn test_hash_file_nonexistent() {
+        let result = hash_file("/nonexistent/file.txt");
+        assert!(result.is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,129 @@
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::process::{Child, Stdio};
+use tauri::api::process::{Command, CommandChild, CommandEvent};
+use tauri::async_runtime::Receiver;
+
+pub struct SidecarOutput {
+    pub stdout: String,
+    pub stderr: String,
+}
+
+pub fn ffmpeg_sidecar() -> &'static str {
+    if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" }
+}
+
+pub fn yt_dlp_sidecar() -> &'static str {
+    if cfg!(target_os = "windows") { "yt-dlp.exe" } else { "yt-dlp" }
+}
+
+// starts a sidecar and hands back its output events and the child so the caller can kill it
+pub fn spawn_sidecar(command: &str, args: &[String]) -> Result<(Receiver<CommandEvent>, CommandChild), String> {
+    Command::new_sidecar(command)
+        .map_err(|e| format!("Failed to create {} command: {}", command, e))?
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn {}: {}", command, e))
+}
+
+// the last lines a process wrote to stderr, used in error messages
+pub fn stderr_tail(stderr: &str, lines: usize) -> String {
+    let tail: Vec<&str> = stderr.lines().rev().take(lines).collect();
+    tail.into_iter().rev().collect::<Vec<&str>>().join("\n")
+}
+
+// runs a sidecar to completion, collecting its output. A non-zero exit code is an error
+// carrying the last lines the process wrote to stderr
+pub async fn run_sidecar(command: &str, args: &[String]) -> Result<SidecarOutput, String> {
+    let (mut rx, _child) = spawn_sidecar(command, args)?;
+
+    let mut stdout: Vec<String> = Vec::new();
+    let mut stderr: Vec<String> = Vec::new();
+    let mut exit_code: Option<i32> = None;
+
+    while let Some(event) = rx.recv().await {
+        match event {
+            CommandEvent::Stdout(line) => stdout.push(line),
+            CommandEvent::Stderr(line) => stderr.push(line),
+            // undecodable output is reported but does not mean the process failed
+            CommandEvent::Error(err) => stderr.push(err),
+            CommandEvent::Terminated(payload) => {
+                exit_code = payload.code;
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let output = SidecarOutput {
+        stdout: stdout.join("\n"),
+        stderr: stderr.join("\n"),
+    };
+
+    match exit_code {
+        Some(0) => Ok(output),
+        code => Err(format!("{} exited with code {:?}: {}", command, code, stderr_tail(&output.stderr, 5))),
+    }
+}
+
+// runs a sidecar on the current thread, handing its raw stdout to `on_output` as it arrives instead of
+// collecting it; command events carry text lines and would mangle binary output. Break stops the
+// process early, which is not an error
+pub fn stream_sidecar<F>(command: &str, args: &[String], mut on_output: F) -> Result<(), String>
+where
+    F: FnMut(&[u8]) -> ControlFlow<()>,
+{
+    let sidecar: Command = Command::new_sidecar(command)
+        .map_err(|e| format!("Failed to create {} command: {}", command, e))?
+        .args(args);
+    let mut child: Child = std::process::Command::from(sidecar)
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to spawn {}: {}", command, e))?;
+
+    // stderr is drained on its own thread so a full pipe cannot stall the process
+    let mut stderr = child.stderr.take().ok_or("Unable to read stderr")?;
+    let stderr_reader = std::thread::spawn(move || {
+        let mut output: String = String::new();
+        let _ = stderr.read_to_string(&mut output);
+        output
+    });
+
+    let mut stdout = child.stdout.take().ok_or("Unable to read stdout")?;
+    let mut buffer: Vec<u8> = vec![0; 64 * 1024];
+    let mut stopped: bool = false;
+    let mut read_error: Option<String> = None;
+    loop {
+        let read: usize = match stdout.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                read_error = Some(format!("Unable to read {} output: {}", command, err));
+                break;
+            }
+        };
+        if on_output(&buffer[..read]).is_break() {
+            stopped = true;
+            break;
+        }
+    }
+    if stopped || read_error.is_some() {
+        let _ = child.kill();
+    }
+    drop(stdout);
+
+    let status = child.wait().map_err(|e| format!("Failed to wait for {}: {}", command, e))?;
+    let stderr: String = stderr_reader.join().unwrap_or_default();
+    if let Some(err) = read_error {
+        return Err(err);
+    }
+    match (stopped, status.code()) {
+        (true, _) | (false, Some(0)) => Ok(()),
+        (false, code) => Err(format!("{} exited with code {:?}: {}", command, code, stderr_tail(&stderr, 5))),
+    }
+}
+
+pub async fn run_ffmpeg(args: &[String]) -> Result<SidecarOutput, String> {
+    run_sidecar(ffmpeg_sidecar(), args).await
+}
@@ -0,0 +1,210 @@
+use lofty::config::WriteOptions;
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::picture::{Picture, PictureType};
+use lofty::probe::Probe;
+use lofty::tag::{Accessor, ItemKey, Tag, TagExt};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TagFields {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub genre: Option<String>,
+    pub comment: Option<String>,
+    // YYYY-MM-DD or a prefix of it
+    pub recording_date: Option<String>,
+}
+
+pub fn read_tag_fields(file_path: &str) -> Result<TagFields, String> {
+    let tagged_file = Probe::open(file_path)
+        .map_err(|e| format!("Unable to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Unable to read tags: {}", e))?;
+
+    let tag = match tagged_file.primary_tag().or(tagged_file.first_tag()) {
+        Some(tag) => tag,
+        None => return Ok(TagFields::default()),
+    };
+
+    Ok(TagFields {
+        title: tag.title().map(|s| s.to_string()),
+        artist: tag.artist().map(|s| s.to_string()),
+        album: tag.album().map(|s| s.to_string()),
+        genre: tag.genre().map(|s| s.to_string()),
+        comment: tag.comment().map(|s| s.to_string()),
+        recording_date: tag.get_string(&ItemKey::RecordingDate).map(|s| s.to_string()),
+    })
+}
+
+// returns the front cover (or the first picture if there is no front cover) embedded in the file
+pub fn read_front_cover(file_path: &str) -> Result<Option<Vec<u8>>, String> {
+    let tagged_file = Probe::open(file_path)
+        .map_err(|e| format!("Unable to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Unable to read tags: {}", e))?;
+
+    let picture = tagged_file.tags().iter().find_map(|tag| {
+        tag.get_picture_type(PictureType::CoverFront)
+            .or(tag.pictures().first())
+    });
+
+    Ok(picture.map(|p| p.data().to_vec()))
+}
+
+// writes the given fields into the primary tag of the file, creating the tag if the file has none.
+// fields that are None are left untouched; cover art replaces any existing front cover
+pub fn write_tag_fields(file_path: &str, fields: &TagFields, cover_art: Option<&[u8]>) -> Result<(), String> {
+    let mut tagged_file = Probe::open(file_path)
+        .map_err(|e| format!("Unable to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Unable to read tags: {}", e))?;
+
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file.primary_tag_mut().ok_or("Unable to create tag for file")?;
+
+    if let Some(title) = &fields.title {
+        tag.set_title(title.clone());
+    }
+    if let Some(artist) = &fields.artist {
+        tag.set_artist(artist.clone());
+    }
+    if let Some(album) = &fields.album {
+        tag.set_album(album.clone());
+    }
+    if let Some(genre) = &fields.genre {
+        tag.set_genre(genre.clone());
+    }
+    if let Some(comment) = &fields.comment {
+        tag.set_comment(comment.clone());
+    }
+    if let Some(recording_date) = &fields.recording_date {
+        tag.insert_text(ItemKey::RecordingDate, recording_date.clone());
+    }
+
+    if let Some(cover_art) = cover_art {
+        let mut picture = Picture::from_reader(&mut &cover_art[..])
+            .map_err(|e| format!("Unsupported cover art: {}", e))?;
+        picture.set_pic_type(PictureType::CoverFront);
+        tag.remove_picture_type(PictureType::CoverFront);
+        tag.push_picture(picture);
+    }
+
+    tagged_file.save_to_path(file_path, WriteOptions::default())
+        .map_err(|e| format!("Unable to write tags: {}", e))?;
+    Ok(())
+}
+
+// writes every tag of the source, pictures included, into the destination, which must have the same format
+pub fn copy_tags(source_path: &str, destination_path: &str) -> Result<(), String> {
+    let tagged_file = Probe::open(source_path)
+        .map_err(|e| format!("Unable to open file: {}", e))?
+        .read()
+        .map_err(|e| format!("Unable to read tags: {}", e))?;
+
+    for tag in tagged_file.tags() {
+        tag.save_to_path(destination_path, WriteOptions::default())
+            .map_err(|e| format!("Unable to write tags: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    use std::path::PathBuf;
+
+    // 1x1 transparent png
+    const PNG_BYTES: [u8; 67] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4,
+        0x89, 0x00, 0x00, 0x00, 0x0A, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9C, 0x63, 0x00, 0x01, 0x00, 0x00,
+        0x05, 0x00, 0x01, 0x0D, 0x0A, 0x2D, 0xB4, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE,
+        0x42, 0x60, 0x82,
+    ];
+
+    fn copy_fixture(dir: &std::path::Path) -> PathBuf {
+        let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/test_fixtures/test_audio_mp3.mp3");
+        let file_path = dir.join("test.mp3");
+        std::fs::copy(fixture, &file_path).unwrap();
+        file_path
+    }
+
+    #[test]
+    fn test_write_and_read_tag_fields() {
+        let dir = tempdir().unwrap();
+        let file_path = copy_fixture(dir.path());
+        let fields = TagFields {
+            title: Some("New Title".to_string()),
+            artist: Some("New Artist".to_string()),
+            album: Some("New Album".to_string()),
+            genre: Some("Ambient".to_string()),
+            comment: Some("edited in chamber".to_string()),
+            recording_date: Some("2024-03-01".to_string()),
+        };
+
+        write_tag_fields(file_path.to_str().unwrap(), &fields, None).unwrap();
+        let read_back = read_tag_fields(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(read_back, fields);
+    }
+
+    #[test]
+    fn test_write_tag_fields_keeps_unset_fields() {
+        let dir = tempdir().unwrap();
+        let file_path = copy_fixture(dir.path());
+        let path = file_path.to_str().unwrap();
+        write_tag_fields(path, &TagFields { album: Some("Kept".to_string()), ..Default::default() }, None).unwrap();
+        write_tag_fields(path, &TagFields { title: Some("Changed".to_string()), ..Default::default() }, None).unwrap();
+
+        let read_back = read_tag_fields(path).unwrap();
+        assert_eq!(read_back.album, Some("Kept".to_string()));
+        assert_eq!(read_back.title, Some("Changed".to_string()));
+    }
+
+    #[test]
+    fn test_write_and_read_front_cover() {
+        let dir = tempdir().unwrap();
+        let file_path = copy_fixture(dir.path());
+        let path = file_path.to_str().unwrap();
+
+        write_tag_fields(path, &TagFields::default(), Some(&PNG_BYTES)).unwrap();
+        assert_eq!(read_front_cover(path).unwrap(), Some(PNG_BYTES.to_vec()));
+    }
+
+    #[test]
+    fn test_write_invalid_cover_art() {
+        let dir = tempdir().unwrap();
+        let file_path = copy_fixture(dir.path());
+        let result = write_tag_fields(file_path.to_str().unwrap(), &TagFields::default(), Some(b"not an image"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_copy_tags() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source.wav");
+        let destination = dir.path().join("destination.wav");
+        for path in [&source, &destination] {
+            crate::helper::decoder::test_utils::write_test_wav(path, 8000, 1, &[0.0; 800]);
+        }
+        let fields = TagFields {
+            title: Some("Copied".to_string()),
+            artist: Some("Artist".to_string()),
+            ..Default::default()
+        };
+        write_tag_fields(source.to_str().unwrap(), &fields, Some(&PNG_BYTES)).unwrap();
+
+        copy_tags(source.to_str().unwrap(), destination.to_str().unwrap()).unwrap();
+        assert_eq!(read_tag_fields(destination.to_str().unwrap()).unwrap(), fields);
+        assert_eq!(read_front_cover(destination.to_str().unwrap()).unwrap(), Some(PNG_BYTES.to_vec()));
+    }
+
+    #[test]
+    fn test_read_tag_fields_nonexistent() {
+        let result = read_tag_fields("/nonexistent/file.mp3");
+        assert!(result.is_err());
+    }
+}
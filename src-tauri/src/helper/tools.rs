@@ -13,6 +13,13 @@ pub fn seconds_to_hh_mm_ss(seconds: u64) -> String {
     res
 }
 
+// millisecond precision timestamp as accepted by ffmpeg, e.g. 00:01:02.345
+pub fn seconds_to_hh_mm_ss_mmm(seconds: f64) -> String {
+    let total_millis: u64 = (seconds.max(0.0) * 1000.0).round() as u64;
+    let millis: u64 = total_millis % 1000;
+    format!("{}.{:03}", seconds_to_hh_mm_ss(total_millis / 1000), millis)
+}
+
 pub fn meta_duration_to_minutes(meta_dur: String) -> String {
     let mut minutes: i32 = 0;
     let mut seconds: i32 = 0;
@@ -79,6 +86,14 @@ mod tests {
         assert_eq!(seconds_to_hh_mm_ss(3661), "01:01:01");
     }
 
+    #[test]
+    fn test_seconds_to_hh_mm_ss_mmm() {
+        assert_eq!(seconds_to_hh_mm_ss_mmm(0.0), "00:00:00.000");
+        assert_eq!(seconds_to_hh_mm_ss_mmm(61.5), "00:01:01.500");
+        assert_eq!(seconds_to_hh_mm_ss_mmm(3661.0426), "01:01:01.043");
+        assert_eq!(seconds_to_hh_mm_ss_mmm(59.9996), "00:01:00.000");
+    }
+
     #[test]
     fn test_meta_duration_to_minutes() {
         assert_eq!(meta_duration_to_minutes("PT1M1S".to_string()), "01:01");
@@ -6,6 +6,11 @@ pub mod helper {
     pub mod files;
     pub mod constants;
     pub mod db_lock;
+    pub mod tags;
+    pub mod artwork;
+    pub mod sidecar;
+    pub mod decoder;
+    pub mod encoder;
 }
 
 
@@ -14,6 +19,10 @@ pub mod models {
   pub mod playlist_model;
   pub mod playlist_audio_model;
   pub mod youtube_model;
+  pub mod remote_audio_model;
+  pub mod recipe_model;
+  pub mod transcode_preset_model;
+  pub mod download_archive_model;
 }
 
 pub mod commands {
@@ -21,12 +30,32 @@ pub mod commands {
     pub mod audio_commands;
     pub mod playlist_audio_commands;
     pub mod audio_buffer;
+    pub mod tag_commands;
+    pub mod artwork_commands;
+    pub mod recipe_commands;
+    pub mod transcode_preset_commands;
+    pub mod download_archive_commands;
     pub mod youtube {
       pub mod yt_web_parser;
+      pub mod yt_dlp_parser;
+      pub mod download_format;
+      pub mod downloader;
       pub mod youtube_commands;
+      pub mod remote_commands;
     }
     pub mod processing {
       pub mod transcode;
       pub mod trimming;
+      pub mod normalize;
+      pub mod split;
+      pub mod effects;
+      pub mod recipe;
+      pub mod concat;
+    }
+    pub mod analysis {
+      pub mod waveform;
+      pub mod loudness;
+      pub mod music;
+      pub mod silence;
     }
 }
@@ -4,10 +4,28 @@ use chamber::commands::audio_buffer::*;
 use chamber::commands::audio_commands::*;
 use chamber::commands::playlist_commands::*;
 use chamber::commands::playlist_audio_commands::*;
+use chamber::commands::tag_commands::*;
+use chamber::commands::artwork_commands::*;
+use chamber::helper::artwork::{resolve_artwork_uri, ARTWORK_URI_SCHEME};
 use chamber::commands::youtube::youtube_commands::*;
+use chamber::commands::youtube::remote_commands::*;
+use chamber::commands::youtube::downloader::*;
 use chamber::commands::processing::transcode::*;
 use chamber::commands::processing::trimming::*;
+use chamber::commands::processing::normalize::*;
+use chamber::commands::processing::split::*;
+use chamber::commands::processing::effects::*;
+use chamber::commands::processing::recipe::*;
+use chamber::commands::processing::concat::*;
+use chamber::commands::recipe_commands::*;
+use chamber::commands::transcode_preset_commands::*;
+use chamber::commands::download_archive_commands::*;
+use chamber::commands::analysis::waveform::*;
+use chamber::commands::analysis::loudness::*;
+use chamber::commands::analysis::music::*;
+use chamber::commands::analysis::silence::*;
 use chamber::db;
+use tauri::http::ResponseBuilder;
 fn main() {
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
@@ -31,8 +49,53 @@ fn main() {
             download_audio,
             transcode_audio,
             trim_single_audio,
-            export_to_destination_driectory
+            export_to_destination_driectory,
+            write_tags,
+            sync_tags_from_file,
+            extract_artwork,
+            set_artwork,
+            remove_artwork,
+            get_artwork_uri,
+            get_waveform,
+            analyze_loudness,
+            search_audio,
+            normalize_audio,
+            analyze_music_library,
+            detect_silence,
+            split_on_silence,
+            split_by_chapters,
+            trim_segments,
+            fade_audio,
+            adjust_gain,
+            reverse_audio,
+            change_speed,
+            shift_pitch,
+            create_recipe,
+            get_all_recipes,
+            update_recipe,
+            delete_recipe,
+            apply_recipe,
+            concat_audio,
+            render_playlist_mix,
+            create_transcode_preset,
+            get_all_transcode_presets,
+            update_transcode_preset,
+            delete_transcode_preset,
+            youtube_expand_url,
+            download_youtube_playlist,
+            get_download_archive,
+            delete_download_archive_entry,
+            clear_download_archive,
+            cancel_download,
+            fetch_remote_metadata,
+            download_remote_audio
          ])
+        .register_uri_scheme_protocol(ARTWORK_URI_SCHEME, |_app, request| {
+            match resolve_artwork_uri(request.uri()) {
+                Ok((bytes, mime)) => ResponseBuilder::new().mimetype(&mime).status(200).body(bytes),
+                Err(err) => ResponseBuilder::new().status(404).body(err.into_bytes()),
+            }
+        })
         .setup(|_app| {
             db::init();
             Ok(())
@@ -1,14 +1,24 @@
 use diesel::prelude::*;
 use serde::{Serialize,Deserialize};
 
-#[derive(Insertable)]
+#[derive(Insertable, Default)]
 #[diesel(table_name = crate::schema::audio)]
 pub struct NewAudio<'a> {
     pub title: &'a str,
     pub author: &'a str,
     pub path: &'a str,
     pub duration: &'a str,
-    pub audio_type: &'a str
+    pub audio_type: &'a str,
+    pub album: Option<&'a str>,
+    pub genre: Option<&'a str>,
+    pub comment: Option<&'a str>,
+    pub artwork_hash: Option<&'a str>,
+    pub source_audio_id: Option<i32>,
+    pub source_video_id: Option<&'a str>,
+    pub source_url: Option<&'a str>,
+    pub source_start: Option<f64>,
+    pub source_end: Option<f64>,
+    pub source_extractor: Option<&'a str>,
   }
 
 #[derive(Debug, diesel::Queryable, AsChangeset)]
@@ -20,7 +30,30 @@ pub struct Audio {
   pub author: String,
   pub path: String,
   pub duration: String,
-  pub audio_type: String
+  pub audio_type: String,
+  pub album: Option<String>,
+  pub genre: Option<String>,
+  pub comment: Option<String>,
+  pub artwork_hash: Option<String>,
+  pub integrated_lufs: Option<f64>,
+  pub loudness_range: Option<f64>,
+  pub true_peak: Option<f64>,
+  // entry this one was derived from by a processing operation
+  pub source_audio_id: Option<i32>,
+  pub bpm: Option<f64>,
+  // e.g. "A minor"
+  pub musical_key: Option<String>,
+  // id of the video the entry was downloaded from
+  pub source_video_id: Option<String>,
+  // url the entry was downloaded from; start and end are set, in seconds, when only a
+  // section of it was downloaded
+  pub source_url: Option<String>,
+  pub source_start: Option<f64>,
+  pub source_end: Option<f64>,
+  // yt-dlp extractor that `source_video_id` belongs to
+  pub source_extractor: Option<String>,
+  // set once the music analysis ran, also when it could not estimate a tempo
+  pub music_analyzed: bool,
 }
 
 #[derive(Debug,Serialize, Deserialize)]
@@ -29,5 +62,36 @@ pub struct AudioArg {
   pub author: Option<String>,
   pub path: Option<String>,
   pub duration: Option<String>,
-  pub audio_type: Option<String>
-}
\ No newline at end of file
+  pub audio_type: Option<String>,
+  pub album: Option<String>,
+  pub genre: Option<String>,
+  pub comment: Option<String>,
+  pub bpm: Option<f64>,
+  pub musical_key: Option<String>,
+}
+
+// filters for search_audio, every field is optional and unset fields do not filter
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AudioFilter {
+  pub query: Option<String>,
+  pub min_integrated_lufs: Option<f64>,
+  pub max_integrated_lufs: Option<f64>,
+  pub min_loudness_range: Option<f64>,
+  pub max_loudness_range: Option<f64>,
+  pub max_true_peak: Option<f64>,
+  pub min_bpm: Option<f64>,
+  pub max_bpm: Option<f64>,
+  pub musical_key: Option<String>,
+  pub sort_by: Option<AudioSort>,
+  pub descending: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioSort {
+  Title,
+  Author,
+  Bpm,
+  MusicalKey,
+  IntegratedLufs,
+}
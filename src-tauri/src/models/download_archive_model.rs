@@ -0,0 +1,24 @@
+use diesel::prelude::*;
+use serde::{Serialize, Deserialize};
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::download_archive)]
+pub struct NewArchiveEntry<'a> {
+    // yt-dlp extractor key in lowercase, e.g. "youtube"
+    pub extractor: &'a str,
+    pub video_id: &'a str,
+    pub title: Option<&'a str>,
+    pub audio_id: Option<i32>,
+}
+
+#[derive(Debug, Queryable, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::download_archive)]
+pub struct ArchiveEntry {
+  pub id: i32,
+  pub extractor: String,
+  pub video_id: String,
+  pub title: Option<String>,
+  // entry created by the download, it may have been deleted since
+  pub audio_id: Option<i32>,
+  pub downloaded_on: String,
+}
@@ -0,0 +1,18 @@
+use diesel::prelude::*;
+use serde::{Serialize, Deserialize};
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::recipe)]
+pub struct NewRecipe<'a> {
+    pub name: &'a str,
+    // JSON list of processing steps
+    pub steps: &'a str,
+}
+
+#[derive(Debug, Queryable, AsChangeset, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::recipe)]
+pub struct Recipe {
+  pub id: i32,
+  pub name: String,
+  pub steps: String,
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RemoteAudio {
+  // lowercase name of the yt-dlp extractor that handles the url, e.g. "youtube" or "soundcloud"
+  pub extractor: Option<String>,
+  // id of the video or track on the source site
+  pub video_id: Option<String>,
+  pub title: Option<String>,
+  pub channel: Option<String>,
+  pub views: Option<String>,
+  pub duration:Option<String>,
+  pub thumbnail: Option<String>,
+  pub url: String,
+  pub duration_seconds: Option<u64>,
+  // YYYY-MM-DD
+  pub upload_date: Option<String>,
+  pub description: Option<String>,
+  pub license: Option<String>,
+  pub webpage_url: Option<String>,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  #[serde(default)]
+  pub chapters: Vec<RemoteChapter>,
+  #[serde(default)]
+  pub thumbnails: Vec<RemoteThumbnail>,
+  // download only this part of the video, in seconds; an unset end means until the end
+  pub section_start: Option<f64>,
+  pub section_end: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteChapter {
+  pub title: String,
+  // seconds from the start of the video
+  pub start_time: f64,
+  pub end_time: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RemoteThumbnail {
+  pub url: String,
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+}
@@ -0,0 +1,18 @@
+use diesel::prelude::*;
+use serde::{Serialize, Deserialize};
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::transcode_preset)]
+pub struct NewTranscodePreset<'a> {
+    pub name: &'a str,
+    // JSON encoded transcode options
+    pub options: &'a str,
+}
+
+#[derive(Debug, Queryable, AsChangeset, Serialize, Deserialize)]
+#[diesel(table_name = crate::schema::transcode_preset)]
+pub struct TranscodePreset {
+  pub id: i32,
+  pub name: String,
+  pub options: String,
+}
@@ -1,11 +1,87 @@
 use serde::{Deserialize, Serialize};
+use crate::models::remote_audio_model::RemoteAudio;
 
-#[derive(Debug,Serialize, Deserialize)]
-pub struct YouTubeAudio{
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum YouTubeUrlKind {
+  Video,
+  Playlist,
+  Channel,
+  Mix,
+}
+
+// one page of the entries behind a playlist, channel or mix url
+#[derive(Debug, Serialize, Deserialize)]
+pub struct YouTubeCollection {
+  pub kind: YouTubeUrlKind,
+  pub id: Option<String>,
   pub title: Option<String>,
-  pub channel: Option<String>,
-  pub views: Option<String>,
-  pub duration:Option<String>,
-  pub thumbnail: Option<String>,
-  pub url: String
-}
\ No newline at end of file
+  pub uploader: Option<String>,
+  // number of entries when the site reports it
+  pub total: Option<u64>,
+  pub page: u32,
+  pub page_size: u32,
+  pub has_more: bool,
+  pub entries: Vec<RemoteAudio>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchDuration {
+  // under 4 minutes
+  Short,
+  // 4 to 20 minutes
+  Medium,
+  // over 20 minutes
+  Long,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchUploadDate {
+  LastHour,
+  Today,
+  ThisWeek,
+  ThisMonth,
+  ThisYear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSort {
+  Relevance,
+  Views,
+  Date,
+}
+
+// every field is optional; without any the search returns the first 20 videos with safe search on
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct YouTubeSearchParams {
+  // results per page, at most 100
+  pub limit: Option<u32>,
+  // `next_page_token` of the previous page
+  pub page_token: Option<String>,
+  pub duration: Option<SearchDuration>,
+  pub upload_date: Option<SearchUploadDate>,
+  pub sort: Option<SearchSort>,
+  pub safe_search: Option<bool>,
+  #[serde(default)]
+  pub include_playlists: bool,
+  #[serde(default)]
+  pub include_channels: bool,
+}
+
+// a search hit; playlists and channels only fill in the title, url and thumbnail
+#[derive(Debug, Serialize, Deserialize)]
+pub struct YouTubeSearchItem {
+  pub kind: YouTubeUrlKind,
+  #[serde(flatten)]
+  pub entry: RemoteAudio,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct YouTubeSearchPage {
+  pub results: Vec<YouTubeSearchItem>,
+  // unset on the last page
+  pub next_page_token: Option<String>,
+}
@@ -8,6 +8,33 @@ diesel::table! {
         path -> Text,
         duration -> Text,
         audio_type -> Text,
+        album -> Nullable<Text>,
+        genre -> Nullable<Text>,
+        comment -> Nullable<Text>,
+        artwork_hash -> Nullable<Text>,
+        integrated_lufs -> Nullable<Double>,
+        loudness_range -> Nullable<Double>,
+        true_peak -> Nullable<Double>,
+        source_audio_id -> Nullable<Integer>,
+        bpm -> Nullable<Double>,
+        musical_key -> Nullable<Text>,
+        source_video_id -> Nullable<Text>,
+        source_url -> Nullable<Text>,
+        source_start -> Nullable<Double>,
+        source_end -> Nullable<Double>,
+        source_extractor -> Nullable<Text>,
+        music_analyzed -> Bool,
+    }
+}
+
+diesel::table! {
+    download_archive (id) {
+        id -> Integer,
+        extractor -> Text,
+        video_id -> Text,
+        title -> Nullable<Text>,
+        audio_id -> Nullable<Integer>,
+        downloaded_on -> Text,
     }
 }
 
@@ -26,11 +53,30 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    recipe (id) {
+        id -> Integer,
+        name -> Text,
+        steps -> Text,
+    }
+}
+
+diesel::table! {
+    transcode_preset (id) {
+        id -> Integer,
+        name -> Text,
+        options -> Text,
+    }
+}
+
 diesel::joinable!(playlist_audio -> audio (audio_id));
 diesel::joinable!(playlist_audio -> playlist (playlist_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     audio,
+    download_archive,
     playlist,
     playlist_audio,
+    recipe,
+    transcode_preset,
 );
@@ -1,6 +1,6 @@
 import { createSignal, createEffect } from "solid-js"
 import { invoke } from "@tauri-apps/api/tauri"
-import { YoutubeQuery } from "~/utils/types"
+import { YoutubeQuery, YoutubeSearchPage } from "~/utils/types"
 import { youtubeQueue, setYoutubeQueue, isSearchDownloading, setIsSearchDownloading } from "~/store/store"
 import { IoRemoveCircleOutline, IoSearchOutline } from "solid-icons/io"
 import { BiRegularLoaderCircle, BiRegularLink, BiRegularAddToQueue } from "solid-icons/bi"
@@ -58,8 +58,8 @@ export const SearchPage = () => {
         const result = await invoke<YoutubeQuery>("youtube_search_by_url", { url: input });
         setYoutubeQuery([result]);
       } else {
-        const result = await invoke<YoutubeQuery[]>("youtube_search", { input });
-        setYoutubeQuery(result);
+        const page = await invoke<YoutubeSearchPage>("youtube_search", { input });
+        setYoutubeQuery(page.results.filter(result => result.kind === "video"));
       }
     } catch (error) {
       return error;
@@ -69,7 +69,11 @@ export const SearchPage = () => {
   async function download(audioList: YoutubeQuery[]) {
     try {
       setIsSearchDownloading(true);
-      await invoke("download_audio", { audioList });
+      const results = await invoke<{ url: string, skipped: boolean, error: string | null }[]>("download_audio", { audioList });
+      const failed = results.filter(result => result.error !== null);
+      if (failed.length > 0) {
+        return new Error(`${failed.length} of ${results.length} downloads failed: ${failed[0].error}`);
+      }
     } catch (error) {
       return new Error(String(error));
     } finally {
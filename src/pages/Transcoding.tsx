@@ -71,8 +71,13 @@ export const Transcoding = () => {
         converted_type: audio_item.converted_type,
         is_added_to_list: audio_item.is_added_to_list
       }));
-      await invoke("transcode_audio", { queueItems });
-      setAudioCodecQueue([]);
+      const results = await invoke<{ path: string, output_path: string | null, error: string | null }[]>("transcode_audio", { queueItems });
+      const failed = results.filter(result => result.error !== null);
+      // keep the failed items queued so they can be retried
+      setAudioCodecQueue(audioCodecQueue.filter(audio_item => failed.some(result => result.path === audio_item.path)));
+      if (failed.length > 0) {
+        return Error(`${failed.length} of ${results.length} items failed: ${failed[0].error}`);
+      }
     } catch(err) {
       return Error(String(err));
     } finally {
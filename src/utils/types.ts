@@ -11,6 +11,22 @@ export type Audio = {
   path: string;
   duration: string;
   audio_type: string;
+  album: string | null;
+  genre: string | null;
+  comment: string | null;
+  artwork_hash: string | null;
+  integrated_lufs: number | null;
+  loudness_range: number | null;
+  true_peak: number | null;
+  source_audio_id: number | null;
+  bpm: number | null;
+  musical_key: string | null;
+  source_video_id: string | null;
+  source_extractor: string | null;
+  source_url: string | null;
+  source_start: number | null;
+  source_end: number | null;
+  music_analyzed: boolean;
 }
 
 export type AudioCodec = Audio & {
@@ -18,13 +34,62 @@ export type AudioCodec = Audio & {
   is_added_to_list: boolean;
 }
 
-export type YoutubeQuery = {
+// metadata of a track on any site yt-dlp supports
+export type RemoteAudio = {
+  extractor?: string | null;
+  video_id?: string | null;
   title: string;
   channel: string;
   views: string;
   duration: string;
   thumbnail: string;
   url: string;
+  duration_seconds?: number | null;
+  upload_date?: string | null;
+  description?: string | null;
+  license?: string | null;
+  webpage_url?: string | null;
+  tags?: string[];
+  chapters?: { title: string, start_time: number, end_time: number }[];
+  thumbnails?: { url: string, width: number | null, height: number | null }[];
+  section_start?: number | null;
+  section_end?: number | null;
+}
+
+// a YouTube query is the remote audio of the youtube extractor
+export type YoutubeQuery = RemoteAudio;
+
+export type YoutubeSearchParams = {
+  limit?: number | null;
+  page_token?: string | null;
+  duration?: "short" | "medium" | "long" | null;
+  upload_date?: "last_hour" | "today" | "this_week" | "this_month" | "this_year" | null;
+  sort?: "relevance" | "views" | "date" | null;
+  safe_search?: boolean | null;
+  include_playlists?: boolean;
+  include_channels?: boolean;
+}
+
+export type YoutubeSearchPage = {
+  results: (YoutubeQuery & { kind: "video" | "playlist" | "channel" | "mix" })[];
+  next_page_token: string | null;
+}
+
+export type DownloadFormat = {
+  keep_original?: boolean;
+  codec?: "opus" | "m4a" | "mp3" | "wav" | "flac" | null;
+  bitrate_kbps?: number | null;
+  vbr_quality?: number | null;
+  max_filesize_mb?: number | null;
+}
+
+export type DownloadOptions = {
+  timeout_secs?: number | null;
+  stall_timeout_secs?: number | null;
+  max_retries?: number | null;
+  format?: DownloadFormat;
+  split_chapters?: boolean;
+  chapter_playlist?: boolean;
 }
 
 export type PlaylistArg = Partial<Playlist>;